@@ -1,12 +1,12 @@
-use std::io;
-
+use crate::backend::Backend;
 use crate::gamemenu::Game;
+use crate::hqr_ress::{DecodeError, Resource};
 use crate::lib3d::func::cross_mult_32;
-use crate::sdl_engine::{delay_ms, SdlEngine};
+use crate::sdl_engine::delay_ms;
 
 const PALETTE_WIDTH: usize = 768;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Palette {
     pub data: [u8; PALETTE_WIDTH],
 }
@@ -19,20 +19,21 @@ impl Default for Palette {
     }
 }
 
-impl TryFrom<Vec<u8>> for Palette {
-    type Error = io::Error;
+impl Resource for Palette {
+    type Error = DecodeError;
 
-    fn try_from(data: Vec<u8>) -> io::Result<Self> {
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
         Ok(Palette {
-            data: data
-                .try_into()
-                .map_err(|_| io::Error::new(io::ErrorKind::Other, "unexpected data size"))?,
+            data: data.try_into().map_err(|_| DecodeError::InvalidSize {
+                expected: PALETTE_WIDTH,
+                actual: data.len(),
+            })?,
         })
     }
 }
 
 #[allow(clippy::identity_op)]
-fn fade_pal(engine: &mut SdlEngine, r: u8, g: u8, b: u8, pal: &Palette, percent: u32) {
+fn fade_pal(engine: &mut dyn Backend, r: u8, g: u8, b: u8, pal: &Palette, percent: u32) {
     let mut workpal = Palette::default();
     for n in 0..256 {
         workpal.data[n * 3 + 0] =
@@ -45,7 +46,7 @@ fn fade_pal(engine: &mut SdlEngine, r: u8, g: u8, b: u8, pal: &Palette, percent:
     engine.palette(&workpal);
 }
 
-pub fn white_fade(engine: &mut SdlEngine) {
+pub fn white_fade(engine: &mut dyn Backend) {
     let mut pal = Palette::default();
     for n in 0..255 {
         pal.data.fill(n);
@@ -54,14 +55,14 @@ pub fn white_fade(engine: &mut SdlEngine) {
     }
 }
 
-pub fn fade_white_to_pal(engine: &mut SdlEngine, pal: &Palette) {
+pub fn fade_white_to_pal(engine: &mut dyn Backend, pal: &Palette) {
     for n in 0..100 {
         fade_pal(engine, 255, 255, 255, pal, n);
         delay_ms(10);
     }
 }
 
-pub fn fade_to_black(engine: &mut SdlEngine, pal: &Palette, flag_black_pal: &mut bool) {
+pub fn fade_to_black(engine: &mut dyn Backend, pal: &Palette, flag_black_pal: &mut bool) {
     if !*flag_black_pal {
         for n in (0..=100).rev().step_by(2) {
             fade_pal(engine, 0, 0, 0, pal, n);
@@ -73,7 +74,7 @@ pub fn fade_to_black(engine: &mut SdlEngine, pal: &Palette, flag_black_pal: &mut
 
 pub fn fade_to_black_pcx(game: &mut Game) {
     fade_to_black(
-        &mut game.engine,
+        game.engine.as_mut(),
         &game.global.palette_pcx,
         &mut game.global.flag_black_pal,
     );
@@ -84,7 +85,7 @@ pub fn set_black_pal(game: &mut Game) {
     game.global.flag_black_pal = true;
 }
 
-pub fn fade_to_pal(engine: &mut SdlEngine, pal: &Palette, flag_black_pal: &mut bool) {
+pub fn fade_to_pal(engine: &mut dyn Backend, pal: &Palette, flag_black_pal: &mut bool) {
     for n in (0..100).step_by(2) {
         fade_pal(engine, 0, 0, 0, pal, n);
         delay_ms(10);
@@ -94,8 +95,28 @@ pub fn fade_to_pal(engine: &mut SdlEngine, pal: &Palette, flag_black_pal: &mut b
 
 pub fn fade_to_pal_pcx(game: &mut Game) {
     fade_to_pal(
-        &mut game.engine,
+        game.engine.as_mut(),
         &game.global.palette_pcx,
         &mut game.global.flag_black_pal,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_palette() {
+        let data: Vec<u8> = (0..PALETTE_WIDTH).map(|n| n as u8).collect();
+        let palette = Palette::decode(&data).unwrap();
+        assert_eq!(palette.data[..], data[..]);
+
+        assert!(matches!(
+            Palette::decode(&data[1..]),
+            Err(DecodeError::InvalidSize {
+                expected: PALETTE_WIDTH,
+                actual: 767
+            })
+        ));
+    }
+}
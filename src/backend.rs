@@ -0,0 +1,70 @@
+use crate::ambiance::Palette;
+use crate::voc::Sample;
+
+/// Output and input of the game: the physical screen, its palette, the sound and the input
+/// events.
+///
+/// The physical screen is an indexed 640x480 buffer which is shown with the current palette.
+pub trait Backend: std::fmt::Debug {
+    /// Sets the palette and shows the physical screen with it.
+    fn palette(&mut self, pal: &Palette);
+
+    /// Copies a full logical screen into the physical screen without showing it.
+    fn copy_from_buffer(&mut self, buf: &[u8]);
+
+    /// Shows the physical screen.
+    fn flip(&mut self);
+
+    /// Copies the rectangle `x0..x1` x `y0..y1` of a logical screen into the physical screen and
+    /// shows it.
+    fn copy_block_phys(&mut self, buf: &[u8], x0: u32, y0: u32, x1: u32, y1: u32);
+
+    fn set_black_pal(&mut self) {
+        self.palette(&Palette::default());
+    }
+
+    /// Copies the physical screen and returns it with the palette it is currently shown with.
+    fn capture(&self) -> (Vec<u8>, Palette);
+
+    /// Returns the next pending input event, if any.
+    fn poll_event(&mut self) -> Option<Event>;
+
+    /// Plays `sample` at `volume` (0 to 255), replacing the sample currently played.
+    fn play_sample(&mut self, sample: &Sample, volume: u32);
+
+    /// Stops the sample currently played.
+    fn stop_sample(&mut self);
+
+    /// Returns whether a sample is still being played.
+    fn is_sample_playing(&self) -> bool;
+}
+
+/// Input event delivered by a [`Backend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The window was closed.
+    Quit,
+    KeyDown(Key),
+    KeyUp(Key),
+}
+
+/// Keys the game reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Escape,
+    Enter,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Any other key
+    Other,
+}
+
+impl Key {
+    /// Whether the key is one of the arrow keys, which repeat while held.
+    pub fn is_arrow(self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::Left | Self::Right)
+    }
+}
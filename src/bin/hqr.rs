@@ -0,0 +1,379 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use lba1_rs::common::RessId;
+use lba1_rs::hqr_ress::{CompressMethod, HqrArchive, HqrWriter};
+
+/// File written by `extract all` with the compression method and file name of each entry.
+///
+/// Each line is `NNN method name` for an entry, `NNN blank` for a blank entry, or `NNN+ method
+/// name` for a hidden entry stored after entry `NNN`.
+const MANIFEST: &str = "manifest.txt";
+
+/// List, extract and repack HQR archives.
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the entries of an archive with their sizes and compression method
+    List { file: PathBuf },
+    /// Extract one entry or all entries of an archive into a directory with their hidden entries,
+    /// all entries are listed in a manifest.txt file
+    Extract {
+        file: PathBuf,
+        /// Entry index or `all`
+        index: String,
+        outdir: PathBuf,
+    },
+    /// Pack the entries listed in the manifest.txt of a directory into an archive, or all files
+    /// of the directory sorted by their index prefix, `NNN+K` files being hidden entries
+    Pack {
+        dir: PathBuf,
+        file: PathBuf,
+        /// Compression of all entries, defaults to the methods of the manifest or lzs
+        #[arg(long, value_enum)]
+        method: Option<Method>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Method {
+    Stored,
+    Lzs,
+    Lzmit,
+}
+
+impl From<Method> for CompressMethod {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Stored => CompressMethod::Stored,
+            Method::Lzs => CompressMethod::Lzs,
+            Method::Lzmit => CompressMethod::Lzmit,
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    match Args::parse().command {
+        Command::List { file } => list(&file),
+        Command::Extract {
+            file,
+            index,
+            outdir,
+        } => extract(&file, &index, &outdir),
+        Command::Pack { dir, file, method } => pack(&dir, &file, method.map(Into::into)),
+    }
+}
+
+fn list(file: &Path) -> anyhow::Result<()> {
+    let mut archive = open(file)?;
+    let is_ress = is_ress(file);
+
+    println!(
+        "{:>5} {:>10} {:>10} {:>8}  name",
+        "index", "size", "stored", "method"
+    );
+    for index in 0..archive.len() {
+        let info = archive.infos()[index];
+        let method = if info.is_blank() {
+            "blank"
+        } else {
+            method_name(info.compress_method)
+        };
+        let name = is_ress.then(|| RessId::from_index(index)).flatten();
+        println!(
+            "{:>5} {:>10} {:>10} {:>8}  {}",
+            index,
+            info.size,
+            info.compressed_size,
+            method,
+            name.map(RessId::name).unwrap_or_default()
+        );
+        for hidden in archive.hidden_infos(index)? {
+            println!(
+                "{:>5} {:>10} {:>10} {:>8}  hidden",
+                format!("{}+", index),
+                hidden.size,
+                hidden.compressed_size,
+                method_name(hidden.compress_method)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn extract(file: &Path, index: &str, outdir: &Path) -> anyhow::Result<()> {
+    let mut archive = open(file)?;
+    let indices = if index == "all" {
+        0..archive.len()
+    } else {
+        let index: usize = index
+            .parse()
+            .with_context(|| format!("invalid entry index '{}'", index))?;
+        if index >= archive.len() {
+            bail!(
+                "entry {} out of bounds, archive has {} entries",
+                index,
+                archive.len()
+            );
+        }
+        index..index + 1
+    };
+
+    fs::create_dir_all(outdir).with_context(|| format!("failed to create {}", outdir.display()))?;
+
+    let is_ress = is_ress(file);
+    let mut manifest = String::new();
+    for index in indices.clone() {
+        let info = archive.infos()[index];
+        if info.is_blank() {
+            manifest.push_str(&format!("{:03} blank\n", index));
+            continue;
+        }
+        let data = archive
+            .entry(index)
+            .with_context(|| format!("failed to read entry {}", index))?;
+        let name = match is_ress.then(|| RessId::from_index(index)).flatten() {
+            Some(id) => format!("{:03}_{}.bin", index, id.name()),
+            None => format!("{:03}.bin", index),
+        };
+        let path = outdir.join(&name);
+        fs::write(&path, data).with_context(|| format!("failed to write {}", path.display()))?;
+        manifest.push_str(&format!(
+            "{:03} {} {}\n",
+            index,
+            method_name(info.compress_method),
+            name
+        ));
+
+        let hidden = archive
+            .hidden_infos(index)
+            .with_context(|| format!("failed to read the hidden entries of {}", index))?;
+        for (n, hidden) in hidden.iter().enumerate() {
+            let data = archive
+                .hidden_entry(index, n)
+                .with_context(|| format!("failed to read hidden entry {}+{}", index, n))?;
+            let name = format!("{:03}+{}.bin", index, n);
+            let path = outdir.join(&name);
+            fs::write(&path, data)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            manifest.push_str(&format!(
+                "{:03}+ {} {}\n",
+                index,
+                method_name(hidden.compress_method),
+                name
+            ));
+        }
+    }
+
+    if indices.len() == archive.len() {
+        let path = outdir.join(MANIFEST);
+        fs::write(&path, manifest)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn pack(dir: &Path, file: &Path, method: Option<CompressMethod>) -> anyhow::Result<()> {
+    let manifest_path = dir.join(MANIFEST);
+    let entries = if manifest_path.is_file() {
+        let manifest = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+        parse_manifest(&manifest)
+            .with_context(|| format!("failed to parse {}", manifest_path.display()))?
+    } else {
+        numbered_files(dir)?
+    };
+
+    let mut writer = HqrWriter::new();
+    let mut after_data = false;
+    for entry in entries {
+        let (entry_method, name, hidden) = match entry {
+            ManifestEntry::Blank => {
+                writer.push_blank();
+                after_data = false;
+                continue;
+            }
+            ManifestEntry::Entry(entry_method, name) => (entry_method, name, false),
+            ManifestEntry::Hidden(entry_method, name) => (entry_method, name, true),
+        };
+        if hidden && !after_data {
+            bail!("hidden entry {} does not follow an entry with data", name);
+        }
+        let path = dir.join(&name);
+        let data = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        if hidden {
+            writer.push_hidden(data, method.unwrap_or(entry_method));
+        } else {
+            writer.push(data, method.unwrap_or(entry_method));
+        }
+        after_data = true;
+    }
+    writer
+        .write(file)
+        .with_context(|| format!("failed to write {}", file.display()))?;
+
+    println!("packed {} entries into {}", writer.len(), file.display());
+    Ok(())
+}
+
+/// Entry of a manifest, or of a directory without manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ManifestEntry {
+    Blank,
+    Entry(CompressMethod, String),
+    /// Hidden entry stored after the previous entry.
+    Hidden(CompressMethod, String),
+}
+
+fn parse_manifest(manifest: &str) -> anyhow::Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    let mut index: usize = 0;
+    for line in manifest.lines().filter(|line| !line.trim().is_empty()) {
+        let mut parts = line.split_whitespace();
+        let first = parts.next().unwrap_or_default();
+        let (line_index, hidden) = match first.strip_suffix('+') {
+            Some(line_index) => (line_index, true),
+            None => (first, false),
+        };
+        let line_index: usize = line_index.parse()?;
+        // hidden entries belong to the previous entry
+        let expected = if hidden {
+            index.checked_sub(1)
+        } else {
+            Some(index)
+        };
+        if expected != Some(line_index) {
+            bail!("unexpected entry {} after {} entries", first, index);
+        }
+        let entry = match (parts.next(), parts.next()) {
+            (Some("blank"), None) if !hidden => ManifestEntry::Blank,
+            (Some(method), Some(name)) => {
+                let method = Method::from_str(method, true).map_err(anyhow::Error::msg)?;
+                let name = name.to_owned();
+                if hidden {
+                    ManifestEntry::Hidden(method.into(), name)
+                } else {
+                    ManifestEntry::Entry(method.into(), name)
+                }
+            }
+            _ => bail!("invalid line '{}'", line),
+        };
+        if !hidden {
+            index += 1;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Files of `dir` sorted by the number they start with, compressed with lzs. Files named
+/// `NNN+K` are the hidden entries of `NNN`, in the order of `K`.
+fn numbered_files(dir: &Path) -> anyhow::Result<Vec<ManifestEntry>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let (index, rest) = split_number(&name)
+            .with_context(|| format!("{} does not start with an entry index", name))?;
+        let hidden = match rest.strip_prefix('+') {
+            Some(rest) => Some(
+                split_number(rest)
+                    .with_context(|| format!("{} has no hidden entry number", name))?
+                    .0,
+            ),
+            None => None,
+        };
+        files.push((index, hidden, name));
+    }
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|(_, hidden, name)| match hidden {
+            Some(_) => ManifestEntry::Hidden(CompressMethod::Lzs, name),
+            None => ManifestEntry::Entry(CompressMethod::Lzs, name),
+        })
+        .collect())
+}
+
+/// Splits the number `s` starts with from the rest.
+fn split_number(s: &str) -> Option<(usize, &str)> {
+    let digits = s.chars().take_while(char::is_ascii_digit).count();
+    Some((s[..digits].parse().ok()?, &s[digits..]))
+}
+
+fn open(file: &Path) -> anyhow::Result<HqrArchive> {
+    HqrArchive::open(file).with_context(|| format!("failed to open {}", file.display()))
+}
+
+fn is_ress(file: &Path) -> bool {
+    file.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.eq_ignore_ascii_case("ress.hqr"))
+}
+
+fn method_name(method: CompressMethod) -> &'static str {
+    match method {
+        CompressMethod::Stored => "stored",
+        CompressMethod::Lzs => "lzs",
+        CompressMethod::Lzmit => "lzmit",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_pack_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("test.hqr");
+        let mut writer = HqrWriter::new();
+        writer.push(vec![7; 100], CompressMethod::Lzs);
+        writer.push_blank();
+        writer.push(vec![1, 2, 3], CompressMethod::Stored);
+        writer.push(vec![5; 200], CompressMethod::Lzmit);
+        writer.push_hidden(vec![6; 100], CompressMethod::Lzs);
+        writer.push_hidden(vec![8, 9], CompressMethod::Stored);
+        for n in 0..1000 {
+            writer.push(vec![n as u8], CompressMethod::Stored);
+        }
+        writer.write(&file).unwrap();
+
+        let outdir = dir.path().join("entries");
+        extract(&file, "all", &outdir).unwrap();
+        let packed = dir.path().join("packed.hqr");
+        pack(&outdir, &packed, None).unwrap();
+        assert_eq!(fs::read(&packed).unwrap(), fs::read(&file).unwrap());
+        let manifest = fs::read_to_string(outdir.join(MANIFEST)).unwrap();
+        let hidden = "003 lzmit 003.bin\n003+ lzs 003+0.bin\n003+ stored 003+1.bin\n";
+        assert!(manifest.contains(hidden));
+
+        // without manifest, the files are sorted by index and compressed with lzs, blank entry 1
+        // has no file
+        fs::remove_file(outdir.join(MANIFEST)).unwrap();
+        pack(&outdir, &packed, None).unwrap();
+        let mut archive = HqrArchive::open(&packed).unwrap();
+        assert_eq!(archive.len(), 1003);
+        assert_eq!(archive.entry(1002).unwrap(), [(999 % 256) as u8]);
+        assert_eq!(archive.infos()[2].compress_method, CompressMethod::Lzs);
+        assert_eq!(archive.hidden_entry(2, 1).unwrap(), [8, 9]);
+        assert_eq!(archive.hidden_infos(2).unwrap().len(), 2);
+
+        // hidden entries can not follow a blank entry
+        let manifest = "000 lzs 000.bin\n001 blank\n001+ stored 003+0.bin\n";
+        assert!(parse_manifest("001 blank\n").is_err());
+        fs::write(outdir.join(MANIFEST), manifest).unwrap();
+        assert!(pack(&outdir, &packed, None).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use lba1_rs::hqr_ress::HqrArchive;
+use lba1_rs::message::Language;
+use lba1_rs::translation::{parse_json, parse_po, read_banks, rebuild, write_json, write_po};
+
+/// Export the texts of text.hqr for translation and import them back.
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Export all banks of all languages, into one JSON file or one PO file per language in a
+    /// directory
+    Export {
+        file: PathBuf,
+        out: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Rebuild text.hqr with the texts of edited JSON or PO files
+    Import {
+        file: PathBuf,
+        out: PathBuf,
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Po,
+}
+
+fn main() -> anyhow::Result<()> {
+    match Args::parse().command {
+        Command::Export { file, out, format } => export(&file, &out, format),
+        Command::Import { file, out, inputs } => import(&file, &out, &inputs),
+    }
+}
+
+fn export(file: &Path, out: &Path, format: Format) -> anyhow::Result<()> {
+    let mut archive = open(file)?;
+    let banks =
+        read_banks(&mut archive).with_context(|| format!("failed to read {}", file.display()))?;
+
+    match format {
+        Format::Json => {
+            let writer = BufWriter::new(create(out)?);
+            write_json(writer, &banks)
+                .with_context(|| format!("failed to write {}", out.display()))?;
+        }
+        Format::Po => {
+            fs::create_dir_all(out)
+                .with_context(|| format!("failed to create {}", out.display()))?;
+            for language in Language::ALL {
+                if !banks.iter().any(|bank| bank.language == language) {
+                    continue;
+                }
+                let path = out.join(format!("{}.po", language.iso_code()));
+                let writer = BufWriter::new(create(&path)?);
+                write_po(writer, &banks, language)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+        }
+    }
+
+    println!("exported {} banks from {}", banks.len(), file.display());
+    Ok(())
+}
+
+fn import(file: &Path, out: &Path, inputs: &[PathBuf]) -> anyhow::Result<()> {
+    let mut banks = Vec::new();
+    for input in inputs {
+        let text = fs::read_to_string(input)
+            .with_context(|| format!("failed to read {}", input.display()))?;
+        let parsed = match input.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => parse_json(&text),
+            Some(ext) if ext.eq_ignore_ascii_case("po") => parse_po(&text),
+            _ => bail!("{}: expected a .json or .po file", input.display()),
+        };
+        banks.extend(parsed.with_context(|| format!("failed to parse {}", input.display()))?);
+    }
+
+    let mut archive = open(file)?;
+    let writer = rebuild(&mut archive, &banks).context("failed to rebuild text archive")?;
+    writer
+        .write(out)
+        .with_context(|| format!("failed to write {}", out.display()))?;
+
+    println!("imported {} banks into {}", banks.len(), out.display());
+    Ok(())
+}
+
+fn open(file: &Path) -> anyhow::Result<HqrArchive> {
+    HqrArchive::open(file).with_context(|| format!("failed to open {}", file.display()))
+}
+
+fn create(path: &Path) -> anyhow::Result<File> {
+    File::create(path).with_context(|| format!("failed to create {}", path.display()))
+}
@@ -0,0 +1,55 @@
+//! Codepage of the game texts and font.
+//!
+//! The texts are stored with the DOS codepage 437, and the glyphs of the font are indexed by the
+//! same codes.
+
+/// Characters of the codes `0x80..=0xFF`.
+const HIGH_CHARS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}', //
+];
+
+/// Returns the character of the code `b`.
+pub fn decode_char(b: u8) -> char {
+    match b {
+        0..=0x7F => char::from(b),
+        _ => HIGH_CHARS[usize::from(b - 0x80)],
+    }
+}
+
+/// Returns the code of the character `c`, if it is part of the codepage.
+pub fn encode_char(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    HIGH_CHARS
+        .iter()
+        .position(|&high| high == c)
+        .map(|idx| idx as u8 + 0x80)
+}
+
+/// Decodes a text of the game.
+pub fn decode(text: &[u8]) -> String {
+    text.iter().copied().map(decode_char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codepage_round_trip() {
+        assert_eq!(decode(b"Options"), "Options");
+        assert_eq!(decode(b"Param\x8atres"), "Paramètres");
+        for b in 0..=u8::MAX {
+            assert_eq!(encode_char(decode_char(b)), Some(b));
+        }
+        assert_eq!(encode_char('€'), None);
+    }
+}
@@ -1,40 +1,329 @@
-#![allow(dead_code)] // TODO: remove
-
-pub const RESS_PAL: usize = 0;
-pub const RESS_FONT_GPM: usize = 1;
-pub const RESS_EMPTY: usize = 2;
-pub const RESS_GOODIES_GPC: usize = 3;
-pub const RESS_SHADOW_GPH: usize = 4;
-pub const RESS_HOLOMAP_PAL: usize = 5;
-pub const RESS_HOLOMAP_HMT: usize = 6;
-pub const RESS_HOLOMAP_HMG: usize = 7;
-pub const RESS_HOLOMAP_POS: usize = 8;
-pub const RESS_HOLO_TWINKEL: usize = 9;
-pub const RESS_HOLO_FLECHE: usize = 10;
-pub const RESS_HOLO_BODYFLECHE: usize = 11;
-pub const RESS_BUMPER_PCR: usize = 12;
-pub const RESS_BUMPER_PAL: usize = 13;
-pub const RESS_MENU_PCR: usize = 14;
-pub const RESS_TWINSUN_PCR: usize = 15;
-pub const RESS_TWINSUN_PAL: usize = 16;
-pub const RESS_INTRO_2_PCR: usize = 17;
-pub const RESS_INTRO_2_PAL: usize = 18;
-pub const RESS_INTRO_3_PCR: usize = 19;
-pub const RESS_INTRO_3_PAL: usize = 20;
-pub const RESS_GAME_OVER: usize = 21;
-pub const RESS_PAL_ALARM: usize = 22;
-pub const RESS_FLA_PCX: usize = 23;
-pub const RESS_PAL_MUSEE: usize = 24;
-pub const RESS_SENDELL_PCR: usize = 25;
-pub const RESS_SENDELL_PAL: usize = 26;
-pub const RESS_LOGO_PCR: usize = 27;
-pub const RESS_LOGO_PAL: usize = 28;
-pub const RESS_HOLO_CONE: usize = 29;
-pub const RESS_HOLOMAP_TRAJ: usize = 30;
-pub const RESS_HOLO_VEHICULE: usize = 31;
-// pub const vehicule de 31 à :usize = 48;
-pub const RESS_BUMPER2_PCR: usize = 49;
-pub const RESS_BUMPER2_PAL: usize = 50;
-pub const RESS_INIT_PLASMA: usize = 51;
-pub const RESS_BUMPER_EA_PCR: usize = 52;
-pub const RESS_BUMPER_EA_PAL: usize = 53;
+use std::borrow::Cow;
+
+pub const NAME_HQR_RESS: &str = "ress.hqr";
+
+/// Number of vehicle models used by the holomap, starting at [`RessId::HoloVehicule`].
+pub const NUM_HOLO_VEHICULES: u8 = 18;
+
+/// Kind of the data stored in an entry of `ress.hqr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RessKind {
+    Palette,
+    Font,
+    Empty,
+    /// Bank of sprites in the brick format
+    Sprites,
+    /// Full-screen 640x480 image
+    Image,
+    /// Holomap textures, meshes, positions and trajectories
+    Holomap,
+    /// 3D model
+    Model,
+    /// Text list mapping FLA movies to PCX slides
+    FlaList,
+    /// Initial state of the plasma effect
+    Plasma,
+}
+
+/// Entry of `ress.hqr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RessId {
+    Pal,
+    FontGpm,
+    Empty,
+    GoodiesGpc,
+    ShadowGph,
+    HolomapPal,
+    HolomapHmt,
+    HolomapHmg,
+    HolomapPos,
+    HoloTwinkel,
+    HoloFleche,
+    HoloBodyfleche,
+    BumperPcr,
+    BumperPal,
+    MenuPcr,
+    TwinsunPcr,
+    TwinsunPal,
+    Intro2Pcr,
+    Intro2Pal,
+    Intro3Pcr,
+    Intro3Pal,
+    GameOver,
+    PalAlarm,
+    FlaPcx,
+    PalMusee,
+    SendellPcr,
+    SendellPal,
+    LogoPcr,
+    LogoPal,
+    HoloCone,
+    HolomapTraj,
+    /// Vehicle models of the holomap
+    HoloVehicule(HoloVehiculeId),
+    Bumper2Pcr,
+    Bumper2Pal,
+    InitPlasma,
+    BumperEaPcr,
+    BumperEaPal,
+}
+
+/// Number of a vehicle model of the holomap, below [`NUM_HOLO_VEHICULES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HoloVehiculeId(u8);
+
+impl HoloVehiculeId {
+    pub fn new(n: u8) -> Option<Self> {
+        (n < NUM_HOLO_VEHICULES).then_some(Self(n))
+    }
+
+    pub fn number(self) -> u8 {
+        self.0
+    }
+}
+
+impl RessId {
+    /// All entries, in the order of the archive.
+    pub fn all() -> impl Iterator<Item = RessId> {
+        (0..).map_while(RessId::from_index)
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Some(match index {
+            0 => Self::Pal,
+            1 => Self::FontGpm,
+            2 => Self::Empty,
+            3 => Self::GoodiesGpc,
+            4 => Self::ShadowGph,
+            5 => Self::HolomapPal,
+            6 => Self::HolomapHmt,
+            7 => Self::HolomapHmg,
+            8 => Self::HolomapPos,
+            9 => Self::HoloTwinkel,
+            10 => Self::HoloFleche,
+            11 => Self::HoloBodyfleche,
+            12 => Self::BumperPcr,
+            13 => Self::BumperPal,
+            14 => Self::MenuPcr,
+            15 => Self::TwinsunPcr,
+            16 => Self::TwinsunPal,
+            17 => Self::Intro2Pcr,
+            18 => Self::Intro2Pal,
+            19 => Self::Intro3Pcr,
+            20 => Self::Intro3Pal,
+            21 => Self::GameOver,
+            22 => Self::PalAlarm,
+            23 => Self::FlaPcx,
+            24 => Self::PalMusee,
+            25 => Self::SendellPcr,
+            26 => Self::SendellPal,
+            27 => Self::LogoPcr,
+            28 => Self::LogoPal,
+            29 => Self::HoloCone,
+            30 => Self::HolomapTraj,
+            31..=48 => Self::HoloVehicule(HoloVehiculeId((index - 31) as u8)),
+            49 => Self::Bumper2Pcr,
+            50 => Self::Bumper2Pal,
+            51 => Self::InitPlasma,
+            52 => Self::BumperEaPcr,
+            53 => Self::BumperEaPal,
+            _ => return None,
+        })
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::Pal => 0,
+            Self::FontGpm => 1,
+            Self::Empty => 2,
+            Self::GoodiesGpc => 3,
+            Self::ShadowGph => 4,
+            Self::HolomapPal => 5,
+            Self::HolomapHmt => 6,
+            Self::HolomapHmg => 7,
+            Self::HolomapPos => 8,
+            Self::HoloTwinkel => 9,
+            Self::HoloFleche => 10,
+            Self::HoloBodyfleche => 11,
+            Self::BumperPcr => 12,
+            Self::BumperPal => 13,
+            Self::MenuPcr => 14,
+            Self::TwinsunPcr => 15,
+            Self::TwinsunPal => 16,
+            Self::Intro2Pcr => 17,
+            Self::Intro2Pal => 18,
+            Self::Intro3Pcr => 19,
+            Self::Intro3Pal => 20,
+            Self::GameOver => 21,
+            Self::PalAlarm => 22,
+            Self::FlaPcx => 23,
+            Self::PalMusee => 24,
+            Self::SendellPcr => 25,
+            Self::SendellPal => 26,
+            Self::LogoPcr => 27,
+            Self::LogoPal => 28,
+            Self::HoloCone => 29,
+            Self::HolomapTraj => 30,
+            Self::HoloVehicule(n) => 31 + usize::from(n.number()),
+            Self::Bumper2Pcr => 49,
+            Self::Bumper2Pal => 50,
+            Self::InitPlasma => 51,
+            Self::BumperEaPcr => 52,
+            Self::BumperEaPal => 53,
+        }
+    }
+
+    /// Name of the entry in the original source code.
+    pub fn name(self) -> Cow<'static, str> {
+        match self {
+            Self::Pal => "RESS_PAL".into(),
+            Self::FontGpm => "RESS_FONT_GPM".into(),
+            Self::Empty => "RESS_EMPTY".into(),
+            Self::GoodiesGpc => "RESS_GOODIES_GPC".into(),
+            Self::ShadowGph => "RESS_SHADOW_GPH".into(),
+            Self::HolomapPal => "RESS_HOLOMAP_PAL".into(),
+            Self::HolomapHmt => "RESS_HOLOMAP_HMT".into(),
+            Self::HolomapHmg => "RESS_HOLOMAP_HMG".into(),
+            Self::HolomapPos => "RESS_HOLOMAP_POS".into(),
+            Self::HoloTwinkel => "RESS_HOLO_TWINKEL".into(),
+            Self::HoloFleche => "RESS_HOLO_FLECHE".into(),
+            Self::HoloBodyfleche => "RESS_HOLO_BODYFLECHE".into(),
+            Self::BumperPcr => "RESS_BUMPER_PCR".into(),
+            Self::BumperPal => "RESS_BUMPER_PAL".into(),
+            Self::MenuPcr => "RESS_MENU_PCR".into(),
+            Self::TwinsunPcr => "RESS_TWINSUN_PCR".into(),
+            Self::TwinsunPal => "RESS_TWINSUN_PAL".into(),
+            Self::Intro2Pcr => "RESS_INTRO_2_PCR".into(),
+            Self::Intro2Pal => "RESS_INTRO_2_PAL".into(),
+            Self::Intro3Pcr => "RESS_INTRO_3_PCR".into(),
+            Self::Intro3Pal => "RESS_INTRO_3_PAL".into(),
+            Self::GameOver => "RESS_GAME_OVER".into(),
+            Self::PalAlarm => "RESS_PAL_ALARM".into(),
+            Self::FlaPcx => "RESS_FLA_PCX".into(),
+            Self::PalMusee => "RESS_PAL_MUSEE".into(),
+            Self::SendellPcr => "RESS_SENDELL_PCR".into(),
+            Self::SendellPal => "RESS_SENDELL_PAL".into(),
+            Self::LogoPcr => "RESS_LOGO_PCR".into(),
+            Self::LogoPal => "RESS_LOGO_PAL".into(),
+            Self::HoloCone => "RESS_HOLO_CONE".into(),
+            Self::HolomapTraj => "RESS_HOLOMAP_TRAJ".into(),
+            Self::HoloVehicule(HoloVehiculeId(0)) => "RESS_HOLO_VEHICULE".into(),
+            Self::HoloVehicule(n) => format!("RESS_HOLO_VEHICULE+{}", n.number()).into(),
+            Self::Bumper2Pcr => "RESS_BUMPER2_PCR".into(),
+            Self::Bumper2Pal => "RESS_BUMPER2_PAL".into(),
+            Self::InitPlasma => "RESS_INIT_PLASMA".into(),
+            Self::BumperEaPcr => "RESS_BUMPER_EA_PCR".into(),
+            Self::BumperEaPal => "RESS_BUMPER_EA_PAL".into(),
+        }
+    }
+
+    pub fn kind(self) -> RessKind {
+        match self {
+            Self::Pal
+            | Self::HolomapPal
+            | Self::BumperPal
+            | Self::TwinsunPal
+            | Self::Intro2Pal
+            | Self::Intro3Pal
+            | Self::PalAlarm
+            | Self::PalMusee
+            | Self::SendellPal
+            | Self::LogoPal
+            | Self::Bumper2Pal
+            | Self::BumperEaPal => RessKind::Palette,
+            Self::FontGpm => RessKind::Font,
+            Self::Empty => RessKind::Empty,
+            Self::GoodiesGpc | Self::ShadowGph => RessKind::Sprites,
+            Self::HolomapHmt | Self::HolomapHmg | Self::HolomapPos | Self::HolomapTraj => {
+                RessKind::Holomap
+            }
+            Self::HoloTwinkel
+            | Self::HoloFleche
+            | Self::HoloBodyfleche
+            | Self::GameOver
+            | Self::HoloCone
+            | Self::HoloVehicule(_) => RessKind::Model,
+            Self::BumperPcr
+            | Self::MenuPcr
+            | Self::TwinsunPcr
+            | Self::Intro2Pcr
+            | Self::Intro3Pcr
+            | Self::SendellPcr
+            | Self::LogoPcr
+            | Self::Bumper2Pcr
+            | Self::BumperEaPcr => RessKind::Image,
+            Self::FlaPcx => RessKind::FlaList,
+            Self::InitPlasma => RessKind::Plasma,
+        }
+    }
+
+    /// Palette to display the entry with, if it is an image or holomap data.
+    pub fn palette(self) -> Option<RessId> {
+        match self.kind() {
+            RessKind::Image => Some(match self {
+                Self::MenuPcr => Self::Pal,
+                _ => Self::from_index(self.index() + 1)?,
+            }),
+            RessKind::Holomap => Some(Self::HolomapPal),
+            _ => None,
+        }
+    }
+
+    pub fn image(self) -> Option<ImageId> {
+        (self.kind() == RessKind::Image).then_some(ImageId(self))
+    }
+}
+
+/// Entry of `ress.hqr` which is known to be a full-screen image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(RessId);
+
+impl ImageId {
+    pub const BUMPER: Self = Self(RessId::BumperPcr);
+    pub const MENU: Self = Self(RessId::MenuPcr);
+    pub const TWINSUN: Self = Self(RessId::TwinsunPcr);
+    pub const INTRO_2: Self = Self(RessId::Intro2Pcr);
+    pub const INTRO_3: Self = Self(RessId::Intro3Pcr);
+    pub const SENDELL: Self = Self(RessId::SendellPcr);
+    pub const LOGO: Self = Self(RessId::LogoPcr);
+    pub const BUMPER2: Self = Self(RessId::Bumper2Pcr);
+    pub const BUMPER_EA: Self = Self(RessId::BumperEaPcr);
+
+    pub fn ress(self) -> RessId {
+        self.0
+    }
+
+    pub fn palette(self) -> RessId {
+        self.0.palette().expect("images have a palette")
+    }
+}
+
+impl From<ImageId> for RessId {
+    fn from(image: ImageId) -> Self {
+        image.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ress_id_index_round_trip() {
+        let count = RessId::all().count();
+        assert_eq!(count, 54);
+        for index in 0..count {
+            let id = RessId::from_index(index).unwrap();
+            assert_eq!(id.index(), index, "{}", id.name());
+        }
+        assert_eq!(RessId::from_index(count), None);
+    }
+
+    #[test]
+    fn test_holo_vehicule() {
+        let last = HoloVehiculeId::new(NUM_HOLO_VEHICULES - 1).unwrap();
+        assert_eq!(RessId::HoloVehicule(last).index(), 48);
+        assert_eq!(RessId::HoloVehicule(last).name(), "RESS_HOLO_VEHICULE+17");
+        assert_eq!(HoloVehiculeId::new(NUM_HOLO_VEHICULES), None);
+    }
+}
@@ -0,0 +1,365 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::global::{VolumeChannel, MAX_VOLUME};
+use crate::message::Language;
+
+/// Name of the configuration file in the game directory.
+pub const NAME_CONFIG: &str = "lba.cfg";
+/// Name of the file written by the installer of the original game.
+pub const NAME_SETUP: &str = "setup.lst";
+
+/// Configuration file made of `Key: value` lines, as written by the original setup.
+///
+/// Lines which are not understood are kept as they are when the file is saved again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    lines: Vec<String>,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(str::to_owned).collect(),
+        }
+    }
+
+    /// Reads the file at `path`. A missing file gives an empty configuration.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Returns the value of `key`, which is matched ignoring case.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .find_map(|line| split_line(line).filter(|(k, _)| k.eq_ignore_ascii_case(key)))
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the value of `key`, replacing its line or appending a new one.
+    pub fn set(&mut self, key: &str, value: impl std::fmt::Display) {
+        for line in &mut self.lines {
+            if let Some((k, _)) = split_line(line).filter(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                *line = format!("{}: {}", k, value);
+                return;
+            }
+        }
+        self.lines.push(format!("{}: {}", key, value));
+    }
+}
+
+impl std::fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Version of the game, which changes some pictures of the intro.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    Us,
+    Europe,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::Europe),
+            "1" => Ok(Self::Us),
+            _ => Err(format!(
+                "unknown version {:?}, expected 0 (Europe) or 1 (USA)",
+                s
+            )),
+        }
+    }
+}
+
+/// How the movies are played.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    /// No movies.
+    None,
+    /// FLA movies of the CD, letterboxed.
+    #[default]
+    Fla,
+    /// FLA movies of the CD stretched to the whole screen, played letterboxed for now.
+    FlaWide,
+    /// Slideshows of PCX pictures instead of the FLA movies. The slideshows are not implemented
+    /// yet, the FLA movies are played if they are installed.
+    FlaPcx,
+}
+
+impl MovieMode {
+    /// Whether the FLA movies of the `fla` directory are played.
+    pub fn plays_fla(self) -> bool {
+        matches!(self, Self::Fla | Self::FlaWide)
+    }
+}
+
+impl FromStr for MovieMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::None),
+            "1" => Ok(Self::Fla),
+            "2" => Ok(Self::FlaWide),
+            "3" => Ok(Self::FlaPcx),
+            _ => Err(format!("unknown movie mode {:?}, expected 0 to 3", s)),
+        }
+    }
+}
+
+/// Language of the voices, `None` if the dialogues are not spoken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Voices(Option<Language>);
+
+impl FromStr for Voices {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("None") {
+            return Ok(Self(None));
+        }
+        s.parse().map(|language| Self(Some(language)))
+    }
+}
+
+/// Settings of the game read from `lba.cfg` and `setup.lst`.
+///
+/// The keys and values are the ones of the `lba.cfg` of the original game, as read by twin-e
+/// (`initConfigurations` in `main.c`):
+///
+/// - `Language`: language of the texts, `LanguageCD`: language of the voices or `None`, with the
+///   names `English`, `Francais`, `Deutsch`, `Espanol` and `Italiano`
+/// - `Version`: 0 for Europe, 1 for USA
+/// - `Movie`: 0 no movies, 1 FLA, 2 wide FLA, 3 PCX slideshows instead of FLA
+/// - `MusicVolume`, `WaveVolume`, `CDVolume`, `LineVolume` and `MasterVolume`: 0 to 255
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub region: Region,
+    pub language: Language,
+    /// Language of the spoken dialogues, `None` without voices. The voices are played with the
+    /// VOX files of the text language.
+    pub voice: Option<Language>,
+    /// Volumes in the order of [`VolumeChannel::ALL`].
+    pub volumes: [u32; 5],
+    pub movies: MovieMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            region: Region::default(),
+            language: Language::default(),
+            voice: Some(Language::default()),
+            volumes: [MAX_VOLUME; 5],
+            movies: MovieMode::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the settings of the game directory `root`. The values of `lba.cfg` take precedence
+    /// over the ones of `setup.lst`, missing or malformed values are replaced with defaults.
+    /// Malformed values are logged as warnings, missing ones only if `setup.lst` exists, as
+    /// `lba.cfg` alone may just hold the volumes saved by the game.
+    pub fn load(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        let mut file = ConfigFile::load(root.join(NAME_SETUP))?;
+        let user = ConfigFile::load(root.join(NAME_CONFIG))?;
+        let complete = !file.lines.is_empty();
+        if !complete && user.lines.is_empty() {
+            log::info!(
+                "no {} or {} in {}, using the default settings",
+                NAME_CONFIG,
+                NAME_SETUP,
+                root.display()
+            );
+            return Ok(Self::default());
+        }
+        file.lines.extend(user.lines);
+
+        let (config, warnings) = Self::read(&file, complete);
+        for warning in warnings {
+            log::warn!("{}", warning);
+        }
+        Ok(config)
+    }
+
+    /// Reads the settings of `file`, with a warning for each missing or malformed value.
+    pub fn from_file(file: &ConfigFile) -> (Self, Vec<String>) {
+        Self::read(file, true)
+    }
+
+    fn read(file: &ConfigFile, warn_missing: bool) -> (Self, Vec<String>) {
+        let mut reader = Reader {
+            file,
+            warn_missing,
+            warnings: Vec::new(),
+        };
+        let default = Self::default();
+        let language = reader.value("Language", default.language);
+        let mut config = Self {
+            region: reader.value("Version", default.region),
+            language,
+            // without LanguageCD the voices are in the language of the texts
+            voice: reader.value("LanguageCD", Voices(Some(language))).0,
+            volumes: default.volumes,
+            movies: reader.value("Movie", default.movies),
+        };
+        for channel in VolumeChannel::ALL {
+            let volume = &mut config.volumes[channel as usize];
+            *volume = reader.value(channel.config_key(), *volume).min(MAX_VOLUME);
+        }
+        (config, reader.warnings)
+    }
+
+    pub fn volume(&self, channel: VolumeChannel) -> u32 {
+        self.volumes[channel as usize]
+    }
+}
+
+struct Reader<'a> {
+    file: &'a ConfigFile,
+    warn_missing: bool,
+    warnings: Vec<String>,
+}
+
+impl Reader<'_> {
+    /// The value of the last line with `key`, or `default`.
+    fn value<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr + std::fmt::Debug,
+        T::Err: std::fmt::Display,
+    {
+        let value = self
+            .file
+            .lines
+            .iter()
+            .rev()
+            .find_map(|line| split_line(line).filter(|(k, _)| k.eq_ignore_ascii_case(key)));
+        let Some((_, value)) = value else {
+            if self.warn_missing {
+                self.warnings
+                    .push(format!("{} is missing, using {:?}", key, default));
+            }
+            return default;
+        };
+        value.parse().unwrap_or_else(|e| {
+            self.warnings
+                .push(format!("{}: {}, using {:?}", key, e, default));
+            default
+        })
+    }
+}
+
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    Some((key.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file() {
+        let mut config = ConfigFile::parse("Language: English\n; comment\nWaveVolume: 100\n");
+        assert_eq!(config.get("language"), Some("English"));
+        assert_eq!(config.get("WaveVolume"), Some("100"));
+        assert_eq!(config.get("MusicVolume"), None);
+
+        config.set("wavevolume", 120);
+        config.set("MusicVolume", 50);
+        assert_eq!(
+            config.to_string(),
+            "Language: English\n; comment\nWaveVolume: 120\nMusicVolume: 50\n"
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let file = ConfigFile::parse(
+            "Version: 0\nLanguage: Francais\nLanguageCD: None\nMovie: 3\nMusicVolume: 100\n\
+             WaveVolume: 300\nCDVolume: 10\nLineVolume: 20\nMasterVolume: 30\n\
+             LanguageCD: Francais\n",
+        );
+        let (config, warnings) = Config::from_file(&file);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(
+            config,
+            Config {
+                region: Region::Europe,
+                language: Language::French,
+                voice: Some(Language::French),
+                volumes: [100, MAX_VOLUME, 10, 20, 30],
+                movies: MovieMode::FlaPcx,
+            }
+        );
+        assert_eq!(config.volume(VolumeChannel::Line), 20);
+
+        let file = ConfigFile::parse("Version: 1\nLanguageCD: none\nMovie: 0\n");
+        let (config, _) = Config::from_file(&file);
+        assert_eq!(config.region, Region::Us);
+        assert_eq!(config.voice, None);
+        assert_eq!(config.movies, MovieMode::None);
+        assert!(!config.movies.plays_fla());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let file = ConfigFile::parse("Version: US\nLanguage: DE\nMovie: 4\nMusicVolume: loud\n");
+        let (config, warnings) = Config::from_file(&file);
+        assert_eq!(
+            config,
+            Config {
+                language: Language::German,
+                voice: Some(Language::German),
+                ..Config::default()
+            }
+        );
+        assert!(warnings[0].starts_with("Version: unknown version"));
+        assert!(warnings[1].starts_with("LanguageCD is missing"));
+        assert!(warnings[2].starts_with("Movie: unknown movie mode"));
+        assert!(warnings[3].starts_with("MusicVolume: invalid digit"));
+        assert_eq!(warnings.len(), 8);
+    }
+
+    #[test]
+    fn test_config_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert_eq!(Config::load(root).unwrap(), Config::default());
+
+        // the volumes saved by the game alone are not reported as incomplete
+        let file = ConfigFile::parse("MusicVolume: 100\n");
+        let (config, warnings) = Config::read(&file, false);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(config.volume(VolumeChannel::Music), 100);
+        file.save(root.join(NAME_CONFIG)).unwrap();
+        assert_eq!(Config::load(root).unwrap(), config);
+
+        fs::write(root.join(NAME_SETUP), "Language: IT\nMusicVolume: 50\n").unwrap();
+        let config = Config::load(root).unwrap();
+        assert_eq!(config.language, Language::Italian);
+        assert_eq!(config.volume(VolumeChannel::Music), 100);
+    }
+}
@@ -0,0 +1,388 @@
+use std::time::{Duration, Instant};
+
+use crate::backend::{Event, Key};
+use crate::common::RessId;
+use crate::font::{Font, TextStyle};
+use crate::gamemenu::{Game, Wait};
+use crate::message::{speak, wait_speak};
+use crate::screen::Rect;
+use crate::sdl_engine::delay_ms;
+
+/// Ends the current line.
+pub const LINE_BREAK: char = '@';
+/// Ends the current page.
+pub const PAGE_BREAK: char = '\x01';
+
+/// Position and look of the dialogue box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialogueBox {
+    pub rect: Rect,
+    /// Space between the border of the box and the text.
+    pub margin: i32,
+    pub line_height: i32,
+    pub style: TextStyle,
+    /// Delay between two revealed characters, the whole page is shown at once if zero.
+    pub char_delay: Duration,
+}
+
+impl Default for DialogueBox {
+    fn default() -> Self {
+        Self {
+            rect: Rect::new(16, 334, 623, 463),
+            margin: 8,
+            line_height: 38,
+            style: TextStyle::new(15).with_shadow(0),
+            char_delay: Duration::from_millis(25),
+        }
+    }
+}
+
+impl DialogueBox {
+    pub fn text_width(&self) -> i32 {
+        self.rect.width() - 2 * self.margin
+    }
+
+    pub fn lines_per_page(&self) -> usize {
+        ((self.rect.height() - 2 * self.margin) / self.line_height).max(1) as usize
+    }
+}
+
+/// Lines of one page of a dialogue.
+pub type Page = Vec<String>;
+
+/// Splits `text` into pages of at most `lines_per_page` lines no wider than `width`.
+///
+/// Lines are wrapped between words, a word wider than `width` gets a line of its own.
+/// [`LINE_BREAK`] and [`PAGE_BREAK`] force a new line or page, other control characters are
+/// flags for the game and are not shown.
+pub fn layout(font: &Font, text: &str, width: i32, lines_per_page: usize) -> Vec<Page> {
+    let mut layout = Layout {
+        font,
+        width,
+        lines_per_page,
+        pages: Vec::new(),
+        page: Vec::new(),
+        line: String::new(),
+    };
+
+    let mut word = String::new();
+    for c in text.chars() {
+        match c {
+            ' ' => layout.push_word(&mut word),
+            LINE_BREAK => {
+                layout.push_word(&mut word);
+                layout.end_line();
+            }
+            PAGE_BREAK => {
+                layout.push_word(&mut word);
+                if !layout.line.is_empty() {
+                    layout.end_line();
+                }
+                layout.end_page();
+            }
+            c if c.is_control() => {}
+            c => word.push(c),
+        }
+    }
+    layout.push_word(&mut word);
+    if !layout.line.is_empty() {
+        layout.end_line();
+    }
+    layout.end_page();
+
+    layout.pages
+}
+
+struct Layout<'a> {
+    font: &'a Font,
+    width: i32,
+    lines_per_page: usize,
+    pages: Vec<Page>,
+    page: Page,
+    line: String,
+}
+
+impl Layout<'_> {
+    fn push_word(&mut self, word: &mut String) {
+        if word.is_empty() {
+            return;
+        }
+        if self.line.is_empty() {
+            self.line = std::mem::take(word);
+            return;
+        }
+        let candidate = format!("{} {}", self.line, word);
+        if self.font.text_width(&candidate) <= self.width {
+            self.line = candidate;
+        } else {
+            self.end_line();
+            self.line = std::mem::take(word);
+        }
+        word.clear();
+    }
+
+    fn end_line(&mut self) {
+        self.page.push(std::mem::take(&mut self.line));
+        if self.page.len() >= self.lines_per_page {
+            self.end_page();
+        }
+    }
+
+    fn end_page(&mut self) {
+        if !self.page.is_empty() {
+            self.pages.push(std::mem::take(&mut self.page));
+        }
+    }
+}
+
+/// Progress of a dialogue shown with the typewriter effect.
+#[derive(Debug, Clone, Default)]
+pub struct Dialogue {
+    pages: Vec<Page>,
+    page: usize,
+    /// Number of characters of the current page which are shown.
+    revealed: usize,
+}
+
+impl Dialogue {
+    pub fn new(pages: Vec<Page>) -> Self {
+        Self {
+            pages,
+            page: 0,
+            revealed: 0,
+        }
+    }
+
+    fn page_len(&self) -> usize {
+        self.pages
+            .get(self.page)
+            .map_or(0, |page| page.iter().map(|line| line.chars().count()).sum())
+    }
+
+    pub fn is_page_complete(&self) -> bool {
+        self.revealed >= self.page_len()
+    }
+
+    pub fn is_last_page(&self) -> bool {
+        self.page + 1 >= self.pages.len()
+    }
+
+    /// Shows `count` more characters of the current page.
+    pub fn reveal(&mut self, count: usize) {
+        self.revealed = (self.revealed + count).min(self.page_len());
+    }
+
+    /// Shows the whole current page.
+    pub fn reveal_all(&mut self) {
+        self.revealed = self.page_len();
+    }
+
+    /// Goes to the next page, returns `false` if the current page is the last one.
+    pub fn next_page(&mut self) -> bool {
+        if self.is_last_page() {
+            return false;
+        }
+        self.page += 1;
+        self.revealed = 0;
+        true
+    }
+
+    /// The shown part of the lines of the current page.
+    pub fn visible_lines(&self) -> Vec<&str> {
+        let Some(page) = self.pages.get(self.page) else {
+            return Vec::new();
+        };
+        let mut remaining = self.revealed;
+        let mut lines = Vec::new();
+        for line in page {
+            if remaining == 0 {
+                break;
+            }
+            let end = line
+                .char_indices()
+                .nth(remaining)
+                .map_or(line.len(), |(idx, _)| idx);
+            remaining -= line[..end].chars().count();
+            lines.push(&line[..end]);
+        }
+        lines
+    }
+}
+
+/// Shows the text `id` of the current bank in the dialogue box of the game and plays its voice.
+///
+/// Any key reveals the whole page or goes to the next page once it is complete, Esc closes the
+/// dialogue. Returns how the dialogue was closed.
+///
+/// Original: `Dial`
+pub fn dial(game: &mut Game, id: u16) -> anyhow::Result<Wait> {
+    game.font = game.resources.ress(RessId::FontGpm)?;
+    let dial_box = game.dialogue_box;
+    let text = game.message.get_text(id).unwrap_or_default().to_owned();
+    let pages = layout(
+        &game.font,
+        &text,
+        dial_box.text_width(),
+        dial_box.lines_per_page(),
+    );
+    let mut dialogue = Dialogue::new(pages);
+
+    speak(game, id)?;
+
+    let mut last_reveal = Instant::now();
+    draw_dialogue(game, &dialogue);
+    loop {
+        // one key press at a time, so that a page is not skipped before it is seen
+        let mut key_pressed = false;
+        while let Some(event) = game.engine.poll_event() {
+            match event {
+                Event::KeyDown(Key::Escape) => {
+                    game.engine.stop_sample();
+                    return Ok(Wait::Escape);
+                }
+                Event::Quit => {
+                    game.engine.stop_sample();
+                    return Ok(Wait::Quit);
+                }
+                Event::KeyDown(_) => {
+                    key_pressed = true;
+                    break;
+                }
+                Event::KeyUp(_) => {}
+            }
+        }
+
+        if !dialogue.is_page_complete() {
+            if key_pressed || dial_box.char_delay.is_zero() {
+                dialogue.reveal_all();
+            } else if last_reveal.elapsed() >= dial_box.char_delay {
+                dialogue.reveal(1);
+                last_reveal = Instant::now();
+            } else {
+                delay_ms(5);
+                continue;
+            }
+            draw_dialogue(game, &dialogue);
+        } else if key_pressed {
+            if !dialogue.next_page() {
+                break;
+            }
+            draw_dialogue(game, &dialogue);
+        } else {
+            delay_ms(10);
+        }
+    }
+
+    Ok(match wait_speak(game) {
+        Some(Wait::Quit) => Wait::Quit,
+        _ => Wait::Done,
+    })
+}
+
+/// Draws the dialogue box with the shown part of the current page over the background screen.
+fn draw_dialogue(game: &mut Game, dialogue: &Dialogue) {
+    let DialogueBox {
+        rect,
+        margin,
+        line_height,
+        style,
+        ..
+    } = game.dialogue_box;
+
+    game.screen
+        .copy_block(rect, &mut game.log, rect.x0, rect.y0);
+    game.log.shade_box(rect, 4);
+    game.log.draw_box_outline(rect, 79);
+
+    let inner = Rect::new(
+        rect.x0 + margin,
+        rect.y0 + margin,
+        rect.x1 - margin,
+        rect.y1 - margin,
+    );
+    for (n, line) in dialogue.visible_lines().into_iter().enumerate() {
+        let y = inner.y0 + n as i32 * line_height;
+        game.font
+            .draw_text_clipped(&mut game.log, inner, inner.x0, y, line, style);
+    }
+
+    game.engine.copy_block_phys(
+        &game.log.data,
+        rect.x0 as u32,
+        rect.y0 as u32,
+        rect.x1 as u32 + 1,
+        rect.y1 as u32 + 1,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::font;
+    use crate::gamemenu::tests::data_dir;
+    use crate::headless_engine::HeadlessEngine;
+
+    #[test]
+    fn test_layout_word_wrap() {
+        // "AA" is 8 pixels wide, a space 8 pixels
+        let pages = layout(&font(), "AA AA AA AA", 24, 3);
+        assert_eq!(pages, [vec!["AA AA", "AA AA"]]);
+
+        let pages = layout(&font(), "AAAAAAAA AA", 24, 3);
+        assert_eq!(pages, [vec!["AAAAAAAA", "AA"]]);
+    }
+
+    #[test]
+    fn test_layout_breaks() {
+        let pages = layout(&font(), "A@B\x01A B\x02 A", 100, 3);
+        assert_eq!(pages, [vec!["A", "B"], vec!["A B A"]]);
+
+        let pages = layout(&font(), "A@A@A@A", 100, 3);
+        assert_eq!(pages, [vec!["A", "A", "A"], vec!["A"]]);
+    }
+
+    #[test]
+    fn test_dialogue_reveal() {
+        let mut dialogue = Dialogue::new(vec![vec!["ab".into(), "cd".into()], vec!["é".into()]]);
+        assert!(dialogue.visible_lines().is_empty());
+
+        dialogue.reveal(3);
+        assert_eq!(dialogue.visible_lines(), ["ab", "c"]);
+        assert!(!dialogue.is_page_complete());
+
+        dialogue.reveal_all();
+        assert_eq!(dialogue.visible_lines(), ["ab", "cd"]);
+        assert!(dialogue.is_page_complete());
+
+        assert!(dialogue.next_page());
+        assert!(dialogue.visible_lines().is_empty());
+        dialogue.reveal(5);
+        assert_eq!(dialogue.visible_lines(), ["é"]);
+        assert!(!dialogue.next_page());
+    }
+
+    #[test]
+    fn test_dial_headless() {
+        let dir = data_dir();
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new(dir.path(), Box::new(engine));
+        game.message.init_dial(&mut game.resources, 0).unwrap();
+        assert!(game.font.glyph(0).is_none());
+
+        // the first key shows the whole page, the second one closes the dialogue
+        state
+            .borrow_mut()
+            .events
+            .extend([Event::KeyDown(Key::Enter), Event::KeyDown(Key::Enter)]);
+        assert_eq!(dial(&mut game, 20).unwrap(), Wait::Done);
+        assert!(game.font.glyph(0).is_some());
+        assert!(state.borrow().events.is_empty());
+
+        state
+            .borrow_mut()
+            .events
+            .extend([Event::KeyUp(Key::Enter), Event::KeyDown(Key::Escape)]);
+        assert_eq!(dial(&mut game, 20).unwrap(), Wait::Escape);
+    }
+}
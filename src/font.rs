@@ -0,0 +1,341 @@
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+
+use crate::codepage;
+use crate::hqr_ress::{read_offset_table, DecodeError, Resource};
+use crate::screen::{Rect, Screen};
+
+/// Default space between two glyphs.
+pub const DEFAULT_INTERLEAVE: i32 = 2;
+/// Default width of a space.
+pub const DEFAULT_SPACE_WIDTH: i32 = 8;
+/// Offset of the drop shadow from the text.
+pub const SHADOW_OFFSET: (i32, i32) = (2, 4);
+
+/// Glyph of the game font.
+///
+/// Every line is stored as alternating lengths of transparent and opaque pixels.
+#[derive(Debug, Clone, Default)]
+pub struct Glyph {
+    pub width: u8,
+    pub height: u8,
+    pub offset_x: u8,
+    pub offset_y: u8,
+    /// Opaque runs of every line as `(x, len)`.
+    lines: Vec<Vec<(u8, u8)>>,
+}
+
+impl Glyph {
+    pub fn lines(&self) -> &[Vec<(u8, u8)>] {
+        &self.lines
+    }
+
+    fn from_reader(mut reader: impl Read) -> Result<Self, DecodeError> {
+        let width = reader.read_u8()?;
+        let height = reader.read_u8()?;
+        let offset_x = reader.read_u8()?;
+        let offset_y = reader.read_u8()?;
+
+        let mut lines = Vec::with_capacity(height.into());
+        for _ in 0..height {
+            let num_values = reader.read_u8()?;
+            let mut runs = Vec::new();
+            let mut x: u8 = 0;
+            for n in 0..num_values {
+                let len = reader.read_u8()?;
+                if n % 2 == 1 {
+                    runs.push((x, len));
+                }
+                x = x
+                    .checked_add(len)
+                    .ok_or(DecodeError::Invalid("glyph line"))?;
+            }
+            lines.push(runs);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            offset_x,
+            offset_y,
+            lines,
+        })
+    }
+}
+
+/// Horizontal alignment of a text relative to its `x` position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+}
+
+/// Colors and alignment used to draw a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    pub color: u8,
+    /// Color of the drop shadow, drawn at [`SHADOW_OFFSET`] below the text.
+    pub shadow: Option<u8>,
+    pub align: Align,
+}
+
+impl TextStyle {
+    pub const fn new(color: u8) -> Self {
+        Self {
+            color,
+            shadow: None,
+            align: Align::Left,
+        }
+    }
+
+    pub const fn centered(self) -> Self {
+        Self {
+            align: Align::Center,
+            ..self
+        }
+    }
+
+    pub const fn with_shadow(self, shadow: u8) -> Self {
+        Self {
+            shadow: Some(shadow),
+            ..self
+        }
+    }
+}
+
+/// The game font from the `RessId::FontGpm` entry of `ress.hqr`.
+#[derive(Debug, Clone)]
+pub struct Font {
+    glyphs: Vec<Glyph>,
+    interleave: i32,
+    space_width: i32,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self {
+            glyphs: Vec::new(),
+            interleave: DEFAULT_INTERLEAVE,
+            space_width: DEFAULT_SPACE_WIDTH,
+        }
+    }
+}
+
+impl Font {
+    pub fn glyph(&self, c: u8) -> Option<&Glyph> {
+        self.glyphs.get(usize::from(c))
+    }
+
+    /// Sets the space between two glyphs and the width of a space.
+    ///
+    /// Original: `SetFont`
+    pub fn set_spacing(&mut self, interleave: i32, space_width: i32) {
+        self.interleave = interleave;
+        self.space_width = space_width;
+    }
+
+    /// Horizontal advance of the character `c`.
+    pub fn char_width(&self, c: u8) -> i32 {
+        match c {
+            b' ' => self.space_width,
+            c => self
+                .glyph(c)
+                .map_or(0, |glyph| i32::from(glyph.width) + self.interleave),
+        }
+    }
+
+    /// Width of `text` in pixels.
+    ///
+    /// Original: `SizeFont`
+    pub fn text_width(&self, text: &str) -> i32 {
+        glyph_indices(text).map(|c| self.char_width(c)).sum()
+    }
+
+    /// Draws `text` with its top at `y`, clipped to the clip rectangle of `screen`.
+    ///
+    /// Original: `Font`
+    pub fn draw_text(&self, screen: &mut Screen, x: i32, y: i32, text: &str, style: TextStyle) {
+        let x = match style.align {
+            Align::Left => x,
+            Align::Center => x - self.text_width(text) / 2,
+        };
+        if let Some(shadow) = style.shadow {
+            self.draw_run(
+                screen,
+                x + SHADOW_OFFSET.0,
+                y + SHADOW_OFFSET.1,
+                text,
+                shadow,
+            );
+        }
+        self.draw_run(screen, x, y, text, style.color);
+    }
+
+    /// Like [`Font::draw_text`], additionally clipped to `rect`.
+    pub fn draw_text_clipped(
+        &self,
+        screen: &mut Screen,
+        rect: Rect,
+        x: i32,
+        y: i32,
+        text: &str,
+        style: TextStyle,
+    ) {
+        let clip = screen.clip();
+        if let Some(rect) = rect.intersect(&clip) {
+            screen.set_clip(rect);
+            self.draw_text(screen, x, y, text, style);
+            screen.set_clip(clip);
+        }
+    }
+
+    fn draw_run(&self, screen: &mut Screen, mut x: i32, y: i32, text: &str, color: u8) {
+        for c in glyph_indices(text) {
+            if c != b' ' {
+                if let Some(glyph) = self.glyph(c) {
+                    self.draw_glyph(screen, glyph, x, y, color);
+                }
+            }
+            x += self.char_width(c);
+        }
+    }
+
+    fn draw_glyph(&self, screen: &mut Screen, glyph: &Glyph, x: i32, y: i32, color: u8) {
+        let x = x + i32::from(glyph.offset_x);
+        let y = y + i32::from(glyph.offset_y);
+        for (line_y, runs) in (y..).zip(glyph.lines()) {
+            for &(run_x, len) in runs {
+                let x0 = x + i32::from(run_x);
+                screen.draw_box(
+                    Rect::new(x0, line_y, x0 + i32::from(len) - 1, line_y),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+/// Glyph indices of the characters of `text`. Characters outside of the codepage are skipped.
+fn glyph_indices(text: &str) -> impl Iterator<Item = u8> + '_ {
+    text.chars().filter_map(codepage::encode_char)
+}
+
+impl Resource for Font {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let glyphs = read_offset_table(data)?
+            .into_iter()
+            .map(|offset| Glyph::from_reader(&data[offset..]))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            glyphs,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::screen::tests::colored_pixels;
+
+    /// Font where `A` is a 2x2 square and `B` a 3 pixel wide line, one pixel down and right.
+    pub(crate) fn font() -> Font {
+        let mut glyphs = vec![Glyph::default(); 256];
+        glyphs[usize::from(b'A')] = Glyph {
+            width: 2,
+            height: 2,
+            offset_x: 0,
+            offset_y: 0,
+            lines: vec![vec![(0, 2)], vec![(0, 2)]],
+        };
+        glyphs[usize::from(b'B')] = Glyph {
+            width: 4,
+            height: 1,
+            offset_x: 1,
+            offset_y: 1,
+            lines: vec![vec![(0, 3)]],
+        };
+        Font {
+            glyphs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_decode_font() {
+        // two glyphs, the second one is 2 pixels wide with a run of 2 pixels after one pixel
+        let data = [8, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 2, 1, 0, 3, 3, 1, 2, 1];
+        let font = Font::decode(&data).unwrap();
+        assert_eq!(font.glyphs.len(), 2);
+        let glyph = font.glyph(1).unwrap();
+        assert_eq!((glyph.width, glyph.height, glyph.offset_y), (2, 1, 3));
+        assert_eq!(glyph.lines(), [vec![(1, 2)]]);
+
+        assert!(matches!(
+            Font::decode(&data[..18]),
+            Err(DecodeError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_text_width() {
+        let font = font();
+        assert_eq!(font.text_width(""), 0);
+        assert_eq!(font.text_width("AB"), 4 + 6);
+        assert_eq!(font.text_width("A A"), 4 + DEFAULT_SPACE_WIDTH + 4);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let font = font();
+        let mut screen = Screen::default();
+        font.draw_text(&mut screen, 10, 0, "AB", TextStyle::new(15));
+        assert_eq!(
+            colored_pixels(&screen),
+            [
+                (10, 0, 15),
+                (11, 0, 15),
+                (10, 1, 15),
+                (11, 1, 15),
+                (15, 1, 15),
+                (16, 1, 15),
+                (17, 1, 15)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw_text_centered_with_shadow() {
+        let font = font();
+        let mut screen = Screen::default();
+        let style = TextStyle::new(15).with_shadow(1).centered();
+        font.draw_text(&mut screen, 12, 0, "A", style);
+        assert_eq!(
+            colored_pixels(&screen),
+            [
+                (10, 0, 15),
+                (11, 0, 15),
+                (10, 1, 15),
+                (11, 1, 15),
+                (12, 4, 1),
+                (13, 4, 1),
+                (12, 5, 1),
+                (13, 5, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw_text_clipped() {
+        let font = font();
+        let mut screen = Screen::default();
+        let rect = Rect::new(11, 0, 20, 0);
+        font.draw_text_clipped(&mut screen, rect, 10, 0, "A", TextStyle::new(15));
+        assert_eq!(colored_pixels(&screen), [(11, 0, 15)]);
+        assert_eq!(screen.clip(), Rect::screen());
+    }
+}
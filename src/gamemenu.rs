@@ -1,237 +1,461 @@
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use crate::ambiance::{fade_to_pal_pcx, fade_white_to_pal, set_black_pal, white_fade};
-use crate::common;
-use crate::global::Global;
-use crate::hqr_ress::load_hqr;
+use crate::backend::{Backend, Event, Key};
+use crate::common::{ImageId, RessId};
+use crate::config::{Config, ConfigFile, NAME_CONFIG};
+use crate::dialogue::DialogueBox;
+use crate::font::{Font, TextStyle};
+use crate::global::{Global, VolumeChannel, MAX_VOLUME};
 use crate::lib3d::func::cross_mult_32;
 use crate::message::Message;
+use crate::plasma::Plasma;
 use crate::playfla::Fla;
-use crate::screen::Screen;
-use crate::sdl_engine::SdlEngine;
+use crate::resources::Resources;
+use crate::screen::{Rect, Screen};
+use crate::screenshot::{save_png, PngFormat};
+use crate::sdl_engine::delay_ms;
+use crate::voc::Sample;
 
 use anyhow::Context as _;
 
-const DEFAULT_HEIGHT: usize = 50;
-const MENU_SPACE: usize = 6;
-const MENU_SIZE: usize = 550;
+const DEFAULT_HEIGHT: i32 = 50;
+const MENU_SPACE: i32 = 6;
+const MENU_SIZE: i32 = 550;
+/// Horizontal center of the entries.
+const MENU_X: i32 = 320;
 const COLOR_SELECT_MENU: u8 = 68;
+/// Delay between two frames of the fire of the selected entry.
+const MENU_FRAME_MS: u64 = 20;
+const MENU_TEXT_STYLE: TextStyle = TextStyle::new(15).with_shadow(0).centered();
 
 #[derive(Debug)]
 pub struct Game {
-    pub engine: SdlEngine,
+    pub engine: Box<dyn Backend>,
 
     pub root: PathBuf,
+    pub resources: Resources,
 
     pub screen: Screen,
     pub log: Screen,
 
     pub global: Global,
+    /// Settings read from the configuration files at startup.
+    pub config: Config,
 
     pub fla: Fla,
     pub message: Message,
+    pub font: Font,
+    pub dialogue_box: DialogueBox,
+    pub plasma: Plasma,
 }
 
-const GAME_MAIN_MENU: &[usize] = &[
-    0,   // selected
-    4,   // num entries
-    200, // y center
-    0,   // .dia num
-    0, 20, // start a new game game
-    0, 21, // continue game
-    0, 23, // options
-    0, 22, // quit
-];
+/// Entry of a menu chosen by the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    /// The entry with this text id was activated.
+    Select(u16),
+    /// The menu was left with Esc.
+    Back,
+    /// The window was closed.
+    Quit,
+}
+
+/// Choice of the player in the main menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuChoice {
+    NewGame,
+    Continue,
+    Options,
+    Quit,
+}
+
+const TEXT_NEW_GAME: u16 = 20;
+const TEXT_CONTINUE: u16 = 21;
+const TEXT_QUIT: u16 = 22;
+const TEXT_OPTIONS: u16 = 23;
+const TEXT_RETURN_OPTIONS: u16 = 24;
+const TEXT_RETURN_VOLUME: u16 = 26;
+const TEXT_VOLUME: u16 = 30;
+const TEXT_SAVE_SETTINGS: u16 = 16;
+
+/// Change of a volume by left or right in the Volume menu.
+const VOLUME_STEP: u32 = 16;
+/// Sample of `samples.hqr` played when a volume is changed. The original menu plays its own test
+/// sound, which is not identified yet, so the first entry of the bank stands in for it.
+const SAMPLE_TEST_VOLUME: usize = 0;
+const NAME_HQR_SAMPLES: &str = "samples.hqr";
+
+/// What an entry of a menu does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEntryKind {
+    Button,
+    /// Bar showing the volume of the channel, changed with left and right.
+    Volume(VolumeChannel),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuEntry {
+    pub kind: MenuEntryKind,
+    /// Label of the entry in the dialogue bank of the menu.
+    pub text_id: u16,
+}
+
+impl MenuEntry {
+    pub const fn button(text_id: u16) -> Self {
+        Self {
+            kind: MenuEntryKind::Button,
+            text_id,
+        }
+    }
+
+    pub const fn volume(channel: VolumeChannel, text_id: u16) -> Self {
+        Self {
+            kind: MenuEntryKind::Volume(channel),
+            text_id,
+        }
+    }
+}
+
+/// Menu made of entries stacked vertically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    pub selected: usize,
+    /// Vertical center of the entries, or 0 to place them at the top of the screen.
+    pub y_center: i32,
+    /// Text bank of the labels.
+    pub dial_bank: usize,
+    pub entries: Vec<MenuEntry>,
+}
+
+impl Menu {
+    pub fn main() -> Self {
+        Self {
+            selected: 0,
+            y_center: 200,
+            dial_bank: 0,
+            entries: vec![
+                MenuEntry::button(TEXT_NEW_GAME),
+                MenuEntry::button(TEXT_CONTINUE),
+                MenuEntry::button(TEXT_OPTIONS),
+                MenuEntry::button(TEXT_QUIT),
+            ],
+        }
+    }
+
+    pub fn options() -> Self {
+        Self {
+            selected: 0,
+            y_center: 0,
+            dial_bank: 0,
+            entries: vec![
+                MenuEntry::button(TEXT_RETURN_OPTIONS),
+                MenuEntry::button(TEXT_VOLUME),
+                MenuEntry::button(46), // saved game management
+                MenuEntry::button(47), // advanced options
+            ],
+        }
+    }
+
+    pub fn volume() -> Self {
+        Self {
+            selected: 0,
+            y_center: 0,
+            dial_bank: 0,
+            entries: vec![
+                MenuEntry::button(TEXT_RETURN_VOLUME),
+                MenuEntry::volume(VolumeChannel::Music, 10),
+                MenuEntry::volume(VolumeChannel::Sample, 11),
+                MenuEntry::volume(VolumeChannel::Cd, 12),
+                MenuEntry::volume(VolumeChannel::Line, 13),
+                MenuEntry::volume(VolumeChannel::Master, 14),
+                MenuEntry::button(TEXT_SAVE_SETTINGS),
+            ],
+        }
+    }
+
+    pub fn selected_entry(&self) -> MenuEntry {
+        self.entries[self.selected]
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.entries.len() - 1) % self.entries.len();
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.entries.len();
+    }
+
+    /// Rectangle of the entry `n` on the screen.
+    pub fn entry_rect(&self, n: usize) -> Rect {
+        let num_entries = self.entries.len() as i32;
+        let top = if self.y_center == 0 {
+            DEFAULT_HEIGHT / 2 + 10
+        } else {
+            self.y_center - (DEFAULT_HEIGHT * num_entries + (num_entries - 1) * MENU_SPACE) / 2
+        };
+        let y = top + n as i32 * (DEFAULT_HEIGHT + MENU_SPACE);
+        Rect::new(
+            MENU_X - MENU_SIZE / 2,
+            y - DEFAULT_HEIGHT / 2,
+            MENU_X + MENU_SIZE / 2,
+            y + DEFAULT_HEIGHT / 2,
+        )
+    }
+}
 
 impl Game {
-    pub fn new(root: impl Into<PathBuf>, engine: SdlEngine) -> Self {
+    pub fn new(root: impl Into<PathBuf>, engine: Box<dyn Backend>) -> Self {
         let root = root.into();
         Self {
             engine,
 
             root: root.clone(),
+            resources: Resources::new(root),
 
             screen: Default::default(),
             log: Default::default(),
 
             global: Default::default(),
+            config: Default::default(),
 
             fla: Default::default(),
-            message: Message::new(root),
+            message: Message::new(),
+            font: Default::default(),
+            dialogue_box: Default::default(),
+            plasma: Default::default(),
         }
     }
 
     pub fn adeline_logo(&mut self) -> anyhow::Result<()> {
-        load_hqr(
-            self.root.join("ress.hqr"),
-            &mut self.screen.data,
-            common::RESS_LOGO_PCR,
-        )
-        .context("failed to load logo pcr from ress.hqr")?;
+        let (image, palette) = self
+            .resources
+            .image(ImageId::LOGO)
+            .context("failed to load logo from ress.hqr")?;
+        self.screen.load_image(&image);
         self.screen.copy_to(&mut self.log);
-        load_hqr(
-            self.root.join("ress.hqr"),
-            &mut self.global.palette_pcx.data,
-            common::RESS_LOGO_PAL,
-        )
-        .context("failed to load logo palette from ress.hqr")?;
-        white_fade(&mut self.engine);
+        self.global.palette_pcx = palette;
+        white_fade(self.engine.as_mut());
         flip(self);
-        fade_white_to_pal(&mut self.engine, &self.global.palette_pcx);
+        fade_white_to_pal(self.engine.as_mut(), &self.global.palette_pcx);
         Ok(())
     }
 
-    pub fn main_game_menu(mut self) -> anyhow::Result<()> {
+    /// Saves the frame currently shown by the backend as PNG.
+    pub fn screenshot(&self, path: impl AsRef<Path>, format: PngFormat) -> io::Result<()> {
+        let (pixels, palette) = self.engine.capture();
+        save_png(path, &pixels, &palette, format)
+    }
+
+    /// Reads the settings of the configuration files of the game directory and applies them.
+    pub fn load_config(&mut self) -> io::Result<()> {
+        self.set_config(Config::load(&self.root)?);
+        Ok(())
+    }
+
+    /// Applies the settings of `config`.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.global.load_volumes(&self.config);
+        self.message.set_language(self.config.language);
+        self.message.set_speak(self.config.voice.is_some());
+    }
+
+    /// Saves the settings into the configuration file of the game directory, keeping its other
+    /// entries.
+    pub fn save_config(&self) -> io::Result<()> {
+        let path = self.root.join(NAME_CONFIG);
+        let mut config = ConfigFile::load(&path)?;
+        self.global.save_volumes(&mut config);
+        config.save(path)
+    }
+
+    /// Runs the main menu until an entry is chosen.
+    pub fn main_game_menu(&mut self) -> anyhow::Result<MenuChoice> {
         // stop sample
 
         self.screen.copy_to(&mut self.log);
-
-        // loop {
-        self.message.init_dial(0)?;
+        flip(self);
 
         // playcdtrack or playmidifile
         // hq_stopsample
 
         // self.get_multi_text(49, )
 
-        self.do_game_menu(GAME_MAIN_MENU)?;
-
-        // break;
-        // }
-
-        Ok(())
+        let mut menu = Menu::main();
+        let choice = match self.do_game_menu(&mut menu)? {
+            MenuAction::Select(TEXT_NEW_GAME) => MenuChoice::NewGame,
+            MenuAction::Select(TEXT_CONTINUE) => MenuChoice::Continue,
+            MenuAction::Select(TEXT_OPTIONS) => MenuChoice::Options,
+            MenuAction::Select(_) | MenuAction::Back | MenuAction::Quit => MenuChoice::Quit,
+        };
+        Ok(choice)
     }
 
-    pub fn do_game_menu(&mut self, menu: &[usize]) -> anyhow::Result<()> {
-        load_hqr(
-            self.root.join("ress.hqr"),
-            &mut self.global.buffer_speak,
-            common::RESS_INIT_PLASMA,
-        )?;
-
-        let selected = menu[0];
-        let num_entries = menu[1];
-
-        // loop {
-        // if menu == game main menu
-        self.draw_game_menu(menu, false)?;
-        // }
-
-        Ok(())
+    /// Runs the Options menu until the player returns to the previous menu. Returns `false` if
+    /// the window was closed.
+    pub fn game_option_menu(&mut self) -> anyhow::Result<bool> {
+        let mut menu = Menu::options();
+        loop {
+            self.screen.copy_to(&mut self.log);
+            flip(self);
+            match self.do_game_menu(&mut menu)? {
+                MenuAction::Select(TEXT_RETURN_OPTIONS) | MenuAction::Back => return Ok(true),
+                MenuAction::Select(TEXT_VOLUME) => {
+                    if !self.volume_menu()? {
+                        return Ok(false);
+                    }
+                }
+                MenuAction::Select(num) => {
+                    log::warn!("option menu entry {} is not implemented yet", num)
+                }
+                MenuAction::Quit => return Ok(false),
+            }
+        }
     }
 
-    // justone???
-    pub fn draw_game_menu(&mut self, menu: &[usize], justone: bool) -> anyhow::Result<()> {
-        let selected = menu[0];
-        let num_entries = menu[1];
-        let mut y = menu[2];
-
-        if y == 0 {
-            y = DEFAULT_HEIGHT / 2 + 10;
-        } else {
-            y -= (DEFAULT_HEIGHT * num_entries + (num_entries - 1) * MENU_SPACE) / 2;
+    /// Runs the Volume menu, the volumes are saved when "Save settings" is chosen. Returns `false`
+    /// if the window was closed.
+    pub fn volume_menu(&mut self) -> anyhow::Result<bool> {
+        let mut menu = Menu::volume();
+        self.screen.copy_to(&mut self.log);
+        flip(self);
+        loop {
+            match self.do_game_menu(&mut menu)? {
+                MenuAction::Select(TEXT_SAVE_SETTINGS) => {
+                    // the game directory may be read only, like the CD, the volumes stay set
+                    if let Err(err) = self.save_config() {
+                        log::warn!("failed to save the settings: {}", err);
+                    }
+                }
+                MenuAction::Select(TEXT_RETURN_VOLUME) | MenuAction::Back => return Ok(true),
+                MenuAction::Select(_) => {}
+                MenuAction::Quit => return Ok(false),
+            }
         }
+    }
 
-        for n in 0..num_entries {
-            let typ = menu[5 + n];
-            let num = menu[5 + n + 1];
-
-            if justone {
-                if n == selected {
-                    self.draw_one_choice(320, y, typ, num, true);
+    /// Shows `menu` and lets the player move the selection with up and down until an entry is
+    /// activated with Enter or the menu is left with Esc. Left and right change the volume of
+    /// volume entries.
+    pub fn do_game_menu(&mut self, menu: &mut Menu) -> anyhow::Result<MenuAction> {
+        self.message
+            .init_dial(&mut self.resources, menu.dial_bank)?;
+        self.font = self.resources.ress(RessId::FontGpm)?;
+        self.plasma = self.resources.ress(RessId::InitPlasma)?;
+
+        self.draw_game_menu(menu, false);
+        loop {
+            while let Some(event) = self.engine.poll_event() {
+                let key = match event {
+                    Event::Quit => return Ok(MenuAction::Quit),
+                    Event::KeyDown(key) => key,
+                    Event::KeyUp(_) => continue,
+                };
+                let entry = menu.selected_entry();
+                match (key, entry.kind) {
+                    (Key::Up | Key::Down, _) => {
+                        self.draw_menu_entry(menu, menu.selected, false);
+                        if key == Key::Up {
+                            menu.select_previous();
+                        } else {
+                            menu.select_next();
+                        }
+                    }
+                    (Key::Left | Key::Right, MenuEntryKind::Volume(channel)) => {
+                        let volume = self.global.volume(channel);
+                        let volume = if key == Key::Left {
+                            volume.saturating_sub(VOLUME_STEP)
+                        } else {
+                            volume + VOLUME_STEP
+                        };
+                        self.global.set_volume(channel, volume);
+                        self.play_test_sound(channel);
+                    }
+                    (Key::Enter | Key::Space, _) => return Ok(MenuAction::Select(entry.text_id)),
+                    (Key::Escape, _) => return Ok(MenuAction::Back),
+                    _ => {}
                 }
-            } else {
-                self.draw_one_choice(320, y, typ, num, n == selected);
             }
 
-            y += DEFAULT_HEIGHT + MENU_SPACE;
+            // animates the fire of the selected entry
+            self.draw_game_menu(menu, true);
+            delay_ms(MENU_FRAME_MS);
         }
-
-        Ok(())
     }
 
-    fn draw_one_choice(&mut self, x: usize, y: usize, typ: usize, num: usize, select: bool) {
-        let x0: u32 = (x - MENU_SIZE / 2) as u32;
-        let x1: u32 = (x + MENU_SPACE / 2) as u32;
-        let mut x2: u32 = 0;
+    /// Draws all entries of `menu`, or only the selected one if `justone` is set.
+    pub fn draw_game_menu(&mut self, menu: &Menu, justone: bool) {
+        for n in 0..menu.entries.len() {
+            if !justone || n == menu.selected {
+                self.draw_menu_entry(menu, n, n == menu.selected);
+            }
+        }
+    }
 
-        let y0: u32 = (y - DEFAULT_HEIGHT / 2) as u32;
-        let y1: u32 = (y + DEFAULT_HEIGHT / 2) as u32;
+    fn draw_menu_entry(&mut self, menu: &Menu, n: usize, select: bool) {
+        self.draw_one_choice(menu.entry_rect(n), menu.entries[n], select);
+    }
 
+    fn draw_one_choice(&mut self, rect: Rect, entry: MenuEntry, select: bool) {
         if select {
-            match typ {
-                1 => {
-                    // music volume
-                    x2 = cross_mult_32(x0, x1, 255, self.global.music_volume);
-                    self.draw_fire(x0, y0, x2, y1, 91 & 0xF0);
-                    self.draw_box(x2, y0, x1, y1, COLOR_SELECT_MENU);
-                }
-                2 => {
-                    // sample volume
-                    x2 = cross_mult_32(x0, x1, 255, self.global.sample_volume);
-                    self.draw_fire(x0, y0, x2, y1, 91 & 0xF0);
-                    self.draw_box(x2, y0, x1, y1, COLOR_SELECT_MENU);
-                }
-                3 => {
-                    // cd volume
-                    x2 = cross_mult_32(x0, x1, 255, self.global.cd_volume);
-                    self.draw_fire(x0, y0, x2, y1, 91 & 0xF0);
-                    self.draw_box(x2, y0, x1, y1, COLOR_SELECT_MENU);
-                }
-                4 => {
-                    // line volume
-                    x2 = cross_mult_32(x0, x1, 255, self.global.line_volume);
-                    self.draw_fire(x0, y0, x2, y1, 91 & 0xF0);
-                    self.draw_box(x2, y0, x1, y1, COLOR_SELECT_MENU);
-                }
-                5 => {
-                    // master volume
-                    x2 = cross_mult_32(x0, x1, 255, self.global.master_volume);
-                    self.draw_fire(x0, y0, x2, y1, 91 & 0xF0);
-                    self.draw_box(x2, y0, x1, y1, COLOR_SELECT_MENU);
-                }
-                _ => {
-                    self.draw_fire(x0, y0, x1, y1, COLOR_SELECT_MENU & 0xF0);
+            match entry.kind {
+                MenuEntryKind::Volume(channel) => {
+                    let volume = self.global.volume(channel);
+                    let x2 = cross_mult_32(rect.x0 as u32, rect.x1 as u32, MAX_VOLUME, volume);
+                    let x2 = x2 as i32;
+                    self.draw_fire(Rect { x1: x2, ..rect }, 91 & 0xF0);
+                    self.log
+                        .draw_box(Rect { x0: x2, ..rect }, COLOR_SELECT_MENU);
                 }
-            }
-
-            if (1..6).contains(&typ) {
-                // TODO: play some sound
+                MenuEntryKind::Button => self.draw_fire(rect, COLOR_SELECT_MENU & 0xF0),
             }
         } else {
-            copy_block(
-                x0,
-                y0,
-                x1,
-                y1,
-                &self.screen.data,
-                x0,
-                y0,
-                &mut self.log.data,
-            );
-            shade_box(x0, y0, x1, y1, 4);
+            self.screen
+                .copy_block(rect, &mut self.log, rect.x0, rect.y0);
+            self.log.shade_box(rect, 4);
         }
 
-        self.draw_frame(x0, y0, x1, y1);
+        self.draw_frame(rect);
 
-        // text
-        // TODO
+        let text = self.message.get_text(entry.text_id).unwrap_or_default();
+        let center_y = (rect.y0 + rect.y1) / 2;
+        self.font
+            .draw_text(&mut self.log, MENU_X, center_y - 18, text, MENU_TEXT_STYLE);
 
         // flip
-        self.engine.copy_block_phys(&self.log.data, x0, y0, x1, y1);
+        self.engine.copy_block_phys(
+            &self.log.data,
+            rect.x0 as u32,
+            rect.y0 as u32,
+            rect.x1 as u32 + 1,
+            rect.y1 as u32 + 1,
+        );
     }
 
-    fn draw_fire(&self, x0: u32, y0: u32, x2: u32, y1: u32, arg: u8) {
-        todo!()
+    /// Plays the test sound at the volume of `channel`.
+    fn play_test_sound(&mut self, channel: VolumeChannel) {
+        // the test sound is optional, the volume menu works without samples.hqr
+        if let Ok(sample) = self
+            .resources
+            .get::<Sample>(NAME_HQR_SAMPLES, SAMPLE_TEST_VOLUME)
+        {
+            let volume = self.global.mixed_volume(channel);
+            self.engine.play_sample(&sample, volume);
+        }
     }
 
-    fn draw_box(&self, x2: u32, y0: u32, x1: u32, y1: u32, color: u8) {
-        todo!()
+    /// Advances the plasma and draws it into `rect` of the log screen.
+    fn draw_fire(&mut self, rect: Rect, color: u8) {
+        self.plasma.update();
+        self.plasma.draw(&mut self.log, rect, color);
     }
 
-    fn draw_frame(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
+    fn draw_frame(&mut self, rect: Rect) {
+        let Rect { x0, y0, x1, y1 } = rect;
         self.log.draw_line(x0, y0, x1, y0, 79);
         self.log.draw_line(x0, y0, x0, y1, 79);
         self.log.draw_line(x1, y0 + 1, x1, y1, 73);
@@ -239,14 +463,6 @@ impl Game {
     }
 }
 
-fn shade_box(x0: u32, y0: u32, x1: u32, y1: u32, arg: i32) {
-    todo!()
-}
-
-fn copy_block(x0_1: u32, y0_1: u32, x1: u32, y1: u32, src: &[u8], xd: u32, yd: u32, dst: &[u8]) {
-    todo!()
-}
-
 pub fn clear(game: &mut Game) {
     game.log.data.fill(0);
 }
@@ -256,34 +472,225 @@ pub fn flip(game: &mut Game) {
     game.engine.flip();
 }
 
-pub fn ress_pict(game: &mut Game, index: usize) -> anyhow::Result<()> {
+pub fn ress_pict(game: &mut Game, id: ImageId) -> anyhow::Result<()> {
     set_black_pal(game);
-    load_hqr(game.root.join("ress.hqr"), &mut game.screen.data, index)?;
+    let (image, palette) = game.resources.image(id)?;
+    game.screen.load_image(&image);
     game.screen.copy_to(&mut game.log);
-    load_hqr(
-        game.root.join("ress.hqr"),
-        &mut game.global.palette_pcx.data,
-        index + 1,
-    )?;
+    game.global.palette_pcx = palette;
     flip(game);
     fade_to_pal_pcx(game);
     Ok(())
 }
 
-pub fn timer_pause(secs: u64) {
-    let now = Instant::now();
-    let dur = Duration::from_secs(secs);
-    while now.elapsed() < dur {
-        // TODO: cancel on key
-        sleep(Duration::from_millis(10));
+/// How a wait for the player, like a logo or a movie, ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    /// The time ran out or a key other than Esc was pressed
+    Done,
+    /// Esc was pressed
+    Escape,
+    /// The window was closed
+    Quit,
+}
+
+/// Returns how the player interrupted the game since the last events were read, if they did.
+pub fn poll_key(game: &mut Game) -> Option<Wait> {
+    while let Some(event) = game.engine.poll_event() {
+        match event {
+            Event::KeyDown(Key::Escape) => return Some(Wait::Escape),
+            Event::KeyDown(_) => return Some(Wait::Done),
+            Event::Quit => return Some(Wait::Quit),
+            Event::KeyUp(_) => {}
+        }
     }
+    None
 }
 
-pub fn timer_esc(secs: u64) {
+/// Waits `secs` seconds, any key ends the wait.
+pub fn timer_esc(game: &mut Game, secs: u64) -> Wait {
     let now = Instant::now();
     let dur = Duration::from_secs(secs);
     while now.elapsed() < dur {
-        // TODO: cancel on key
+        if let Some(wait) = poll_key(game) {
+            return wait;
+        }
         sleep(Duration::from_millis(10));
     }
+    Wait::Done
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::common::NAME_HQR_RESS;
+    use crate::headless_engine::HeadlessEngine;
+    use crate::hqr_ress::{CompressMethod, HqrWriter};
+    use crate::message::{TextBank, TextOrder, NAME_HQR_TEXT};
+    use crate::plasma::{PLASMA_HEIGHT, PLASMA_WIDTH};
+    use tempfile::TempDir;
+
+    /// Writes a `ress.hqr` with an empty font and plasma into a new temporary directory.
+    pub(crate) fn data_dir() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut writer = HqrWriter::new();
+        for id in RessId::all() {
+            let data = match id {
+                RessId::FontGpm => vec![4, 0, 0, 0, 0, 0, 0, 0],
+                RessId::InitPlasma => vec![0; 2 * PLASMA_WIDTH * PLASMA_HEIGHT],
+                _ => Vec::new(),
+            };
+            writer.push(data, CompressMethod::Stored);
+        }
+        writer.write(root.join(NAME_HQR_RESS)).unwrap();
+
+        // English bank of the menus
+        let ids = [TEXT_NEW_GAME, TEXT_CONTINUE, TEXT_OPTIONS, TEXT_QUIT];
+        let order = TextOrder { ids: ids.to_vec() };
+        let bank = TextBank::from_texts(&["New game", "Continue", "Options", "Quit"]).unwrap();
+        let mut writer = HqrWriter::new();
+        writer.push(order.to_bytes(), CompressMethod::Stored);
+        writer.push(bank.as_bytes().to_vec(), CompressMethod::Stored);
+        writer.write(root.join(NAME_HQR_TEXT)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_menu_layout() {
+        let menu = Menu::main();
+        assert_eq!(menu.entry_rect(0), Rect::new(45, 66, 595, 116));
+        assert_eq!(menu.entry_rect(3), Rect::new(45, 234, 595, 284));
+        assert_eq!(menu.entry_rect(0).width(), MENU_SIZE + 1);
+
+        // entries are placed at the top of the screen without a center
+        let menu = Menu::volume();
+        assert_eq!(menu.entry_rect(0), Rect::new(45, 10, 595, 60));
+        assert_eq!(menu.entry_rect(6), Rect::new(45, 346, 595, 396));
+        assert!((0..menu.entries.len()).all(|n| {
+            Rect::screen().intersect(&menu.entry_rect(n)) == Some(menu.entry_rect(n))
+        }));
+    }
+
+    #[test]
+    fn test_menu_selection_wraps() {
+        let mut menu = Menu::main();
+        menu.select_previous();
+        assert_eq!(menu.selected_entry(), MenuEntry::button(TEXT_QUIT));
+        menu.select_next();
+        menu.select_next();
+        assert_eq!(menu.selected_entry(), MenuEntry::button(TEXT_CONTINUE));
+    }
+
+    #[test]
+    fn test_timer_esc() {
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new("", Box::new(engine));
+
+        let now = Instant::now();
+        state.borrow_mut().events.extend([
+            Event::KeyUp(Key::Escape),
+            Event::KeyDown(Key::Enter),
+            Event::KeyDown(Key::Escape),
+        ]);
+        assert_eq!(timer_esc(&mut game, 10), Wait::Done);
+        assert_eq!(timer_esc(&mut game, 10), Wait::Escape);
+        state.borrow_mut().events.push_back(Event::Quit);
+        assert_eq!(timer_esc(&mut game, 10), Wait::Quit);
+        assert!(now.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(timer_esc(&mut game, 0), Wait::Done);
+    }
+
+    #[test]
+    fn test_game_menu_navigation() {
+        let dir = data_dir();
+        let root = dir.path();
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new(root, Box::new(engine));
+
+        state.borrow_mut().events.extend([
+            Event::KeyDown(Key::Up),
+            Event::KeyUp(Key::Up),
+            Event::KeyDown(Key::Up),
+            Event::KeyDown(Key::Enter),
+        ]);
+        let mut menu = Menu::main();
+        let action = game.do_game_menu(&mut menu).unwrap();
+        assert_eq!(action, MenuAction::Select(TEXT_OPTIONS));
+        assert_eq!(menu.selected, 2);
+
+        state
+            .borrow_mut()
+            .events
+            .push_back(Event::KeyDown(Key::Escape));
+        assert_eq!(game.do_game_menu(&mut menu).unwrap(), MenuAction::Back);
+        assert_eq!(menu.selected, 2);
+    }
+
+    #[test]
+    fn test_volume_menu() {
+        let dir = data_dir();
+        let root = dir.path();
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new(root, Box::new(engine));
+
+        state.borrow_mut().events.extend([
+            Event::KeyDown(Key::Down),
+            Event::KeyDown(Key::Left),
+            Event::KeyDown(Key::Left),
+            Event::KeyDown(Key::Down),
+            Event::KeyDown(Key::Right),
+            Event::KeyDown(Key::Escape),
+        ]);
+        let mut menu = Menu::volume();
+        assert_eq!(game.do_game_menu(&mut menu).unwrap(), MenuAction::Back);
+        assert_eq!(game.global.music_volume, 255 - 2 * VOLUME_STEP);
+        assert_eq!(game.global.sample_volume, 255);
+
+        game.save_config().unwrap();
+        let mut restored = Game::new(root, Box::new(HeadlessEngine::new()));
+        restored.load_config().unwrap();
+        assert_eq!(restored.global.music_volume, 255 - 2 * VOLUME_STEP);
+    }
+
+    #[test]
+    fn test_volume_menu_saves_on_request() {
+        let dir = data_dir();
+        let root = dir.path();
+        let config = root.join(NAME_CONFIG);
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new(root, Box::new(engine));
+
+        // leaving the menu does not save
+        state
+            .borrow_mut()
+            .events
+            .push_back(Event::KeyDown(Key::Escape));
+        assert!(game.volume_menu().unwrap());
+        assert!(!config.exists());
+
+        // a failed save is not an error of the menu
+        std::fs::create_dir(&config).unwrap();
+        state.borrow_mut().events.extend([
+            Event::KeyDown(Key::Up),
+            Event::KeyDown(Key::Enter),
+            Event::KeyDown(Key::Escape),
+        ]);
+        assert!(game.volume_menu().unwrap());
+        assert!(config.is_dir());
+
+        std::fs::remove_dir(&config).unwrap();
+        state.borrow_mut().events.extend([
+            Event::KeyDown(Key::Up),
+            Event::KeyDown(Key::Enter),
+            Event::Quit,
+        ]);
+        assert!(!game.volume_menu().unwrap());
+        assert!(config.is_file());
+    }
 }
@@ -1,4 +1,38 @@
 use crate::ambiance::Palette;
+use crate::config::{Config, ConfigFile};
+
+pub const MAX_VOLUME: u32 = 255;
+
+/// Volume settings of the Volume menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChannel {
+    Music,
+    Sample,
+    Cd,
+    Line,
+    Master,
+}
+
+impl VolumeChannel {
+    pub const ALL: [VolumeChannel; 5] = [
+        Self::Music,
+        Self::Sample,
+        Self::Cd,
+        Self::Line,
+        Self::Master,
+    ];
+
+    /// Key of the volume in the configuration file.
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Self::Music => "MusicVolume",
+            Self::Sample => "WaveVolume",
+            Self::Cd => "CDVolume",
+            Self::Line => "LineVolume",
+            Self::Master => "MasterVolume",
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Global {
@@ -7,7 +41,7 @@ pub struct Global {
 
     pub flag_black_pal: bool,
 
-    pub buffer_speak: [u8; 256 * 1024 + 34],
+    pub buffer_speak: Box<[u8]>,
 
     pub sample_volume: u32,
     pub music_volume: u32,
@@ -22,12 +56,56 @@ impl Default for Global {
             palette_pcx: Default::default(),
             palette: Default::default(),
             flag_black_pal: Default::default(),
-            buffer_speak: [0; 256 * 1024 + 34],
-            sample_volume: 0,
-            music_volume: 0,
-            cd_volume: 0,
-            line_volume: 0,
-            master_volume: 0,
+            buffer_speak: vec![0; 256 * 1024 + 34].into(),
+            sample_volume: MAX_VOLUME,
+            music_volume: MAX_VOLUME,
+            cd_volume: MAX_VOLUME,
+            line_volume: MAX_VOLUME,
+            master_volume: MAX_VOLUME,
+        }
+    }
+}
+
+impl Global {
+    pub fn volume(&self, channel: VolumeChannel) -> u32 {
+        match channel {
+            VolumeChannel::Music => self.music_volume,
+            VolumeChannel::Sample => self.sample_volume,
+            VolumeChannel::Cd => self.cd_volume,
+            VolumeChannel::Line => self.line_volume,
+            VolumeChannel::Master => self.master_volume,
+        }
+    }
+
+    pub fn set_volume(&mut self, channel: VolumeChannel, volume: u32) {
+        let volume = volume.min(MAX_VOLUME);
+        match channel {
+            VolumeChannel::Music => self.music_volume = volume,
+            VolumeChannel::Sample => self.sample_volume = volume,
+            VolumeChannel::Cd => self.cd_volume = volume,
+            VolumeChannel::Line => self.line_volume = volume,
+            VolumeChannel::Master => self.master_volume = volume,
+        }
+    }
+
+    /// Volume of `channel` scaled by the master volume.
+    pub fn mixed_volume(&self, channel: VolumeChannel) -> u32 {
+        match channel {
+            VolumeChannel::Master => self.master_volume,
+            channel => self.volume(channel) * self.master_volume / MAX_VOLUME,
+        }
+    }
+
+    /// Restores the volumes read from the configuration files.
+    pub fn load_volumes(&mut self, config: &Config) {
+        for channel in VolumeChannel::ALL {
+            self.set_volume(channel, config.volume(channel));
+        }
+    }
+
+    pub fn save_volumes(&self, config: &mut ConfigFile) {
+        for channel in VolumeChannel::ALL {
+            config.set(channel.config_key(), self.volume(channel));
         }
     }
 }
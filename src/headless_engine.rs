@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::ambiance::Palette;
+use crate::backend::{Backend, Event};
+use crate::screen::{HEIGHT, WIDTH};
+use crate::voc::Sample;
+
+/// In-memory backend without a display or sound.
+///
+/// Every presented frame, every set palette and every played sample is recorded, and input events
+/// are replayed from a queue. The state is shared with the handle returned by
+/// [`HeadlessEngine::state`], so that the game can be driven and inspected in tests after the
+/// engine was moved into it.
+#[derive(Debug)]
+pub struct HeadlessEngine {
+    buffer: Box<[u8]>,
+    state: Rc<RefCell<HeadlessState>>,
+}
+
+/// Recorded output and pending input of a [`HeadlessEngine`].
+#[derive(Debug, Default)]
+pub struct HeadlessState {
+    /// Frames in the order they were shown.
+    pub frames: Vec<Box<[u8]>>,
+    /// Palettes in the order they were set.
+    pub palettes: Vec<Palette>,
+    /// Input events returned by [`Backend::poll_event`].
+    pub events: VecDeque<Event>,
+    /// Samples with their volume in the order they were played. Samples end immediately.
+    pub samples: Vec<(Sample, u32)>,
+}
+
+impl Default for HeadlessEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeadlessEngine {
+    pub fn new() -> Self {
+        Self {
+            buffer: vec![0; WIDTH * HEIGHT].into(),
+            state: Default::default(),
+        }
+    }
+
+    pub fn state(&self) -> Rc<RefCell<HeadlessState>> {
+        self.state.clone()
+    }
+}
+
+impl Backend for HeadlessEngine {
+    fn palette(&mut self, pal: &Palette) {
+        self.state.borrow_mut().palettes.push(pal.clone());
+    }
+
+    fn copy_from_buffer(&mut self, buf: &[u8]) {
+        self.buffer.copy_from_slice(buf);
+    }
+
+    fn flip(&mut self) {
+        self.state.borrow_mut().frames.push(self.buffer.clone());
+    }
+
+    fn copy_block_phys(&mut self, buf: &[u8], x0: u32, y0: u32, x1: u32, y1: u32) {
+        let offset = x0 as usize;
+        let len = x1.saturating_sub(x0) as usize;
+        for y in y0..y1 {
+            let line = y as usize * WIDTH;
+            self.buffer[line + offset..line + offset + len]
+                .copy_from_slice(&buf[line + offset..line + offset + len]);
+        }
+        self.flip();
+    }
+
+    fn capture(&self) -> (Vec<u8>, Palette) {
+        let palette = self.state.borrow().palettes.last().cloned();
+        (self.buffer.to_vec(), palette.unwrap_or_default())
+    }
+
+    fn poll_event(&mut self) -> Option<Event> {
+        self.state.borrow_mut().events.pop_front()
+    }
+
+    fn play_sample(&mut self, sample: &Sample, volume: u32) {
+        self.state
+            .borrow_mut()
+            .samples
+            .push((sample.clone(), volume));
+    }
+
+    fn stop_sample(&mut self) {}
+
+    fn is_sample_playing(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ambiance::{fade_to_pal, set_black_pal};
+    use crate::gamemenu::{flip, Game};
+
+    #[test]
+    fn test_headless_records_frames_and_palettes() {
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new("missing", Box::new(engine));
+
+        set_black_pal(&mut game);
+        game.log.data[WIDTH + 1] = 42;
+        flip(&mut game);
+
+        game.global.palette.data.fill(63);
+        fade_to_pal(
+            game.engine.as_mut(),
+            &game.global.palette,
+            &mut game.global.flag_black_pal,
+        );
+
+        let state = state.borrow();
+        assert_eq!(state.frames.len(), 1);
+        assert_eq!(&state.frames[0][..], &game.log.data[..]);
+        assert_eq!(state.palettes.first(), Some(&Palette::default()));
+        assert_eq!(state.palettes.len(), 51);
+        assert!(state.palettes.last().unwrap().data.iter().all(|&c| c == 61));
+        assert!(!game.global.flag_black_pal);
+    }
+}
@@ -1,76 +1,448 @@
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::libsys::decompress_lzs;
+use crate::libsys::{compress_lzs, decompress_lzs, LZMIT_MIN_LEN, LZS_MIN_LEN};
 
-pub fn load_hqr(path: impl AsRef<Path>, buffer: &mut [u8], index: usize) -> io::Result<usize> {
-    let (file, header) = read_header(path, index)?;
+/// Size of the per-entry header preceding the data of each block.
+const HEADER_SIZE: u64 = 10;
+
+/// An opened HQR archive with its offset table parsed into an in-memory index.
+///
+/// The table is read once on open; afterwards, loading an entry only costs a seek and a read.
+#[derive(Debug)]
+pub struct HqrArchive<R = BufReader<File>> {
+    reader: R,
+    entries: Vec<EntryInfo>,
+    file_len: u64,
+}
+
+/// Metadata of a single entry of an HQR archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// Offset of the entry header in the archive.
+    pub offset: u64,
+    /// Size of the decompressed data.
+    pub size: usize,
+    /// Size of the data as stored in the archive.
+    pub compressed_size: usize,
+    pub compress_method: CompressMethod,
+}
+
+impl EntryInfo {
+    /// Blank entries have a zero offset in the table and no data.
+    pub fn is_blank(&self) -> bool {
+        self.offset == 0
+    }
+}
+
+impl HqrArchive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path.as_ref())?))
+    }
+}
+
+impl<R: Read + Seek> HqrArchive<R> {
+    pub fn from_reader(mut reader: R) -> io::Result<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let num_blocks = reader.read_u32::<LittleEndian>()? as usize / 4;
+        if num_blocks == 0 || num_blocks as u64 * 4 > file_len {
+            return Err(io::Error::other("invalid offset table"));
+        }
+
+        let mut offsets = Vec::with_capacity(num_blocks);
+        offsets.push(num_blocks as u64 * 4);
+        for _ in 1..num_blocks {
+            offsets.push(reader.read_u32::<LittleEndian>()?.into());
+        }
+
+        // Note: the last offset of the table points to the end of the file and is not an entry.
+        if offsets.last() == Some(&file_len) {
+            offsets.pop();
+        }
+
+        let mut entries = Vec::with_capacity(offsets.len());
+        for offset in offsets {
+            if offset == 0 {
+                entries.push(EntryInfo {
+                    offset,
+                    size: 0,
+                    compressed_size: 0,
+                    compress_method: CompressMethod::Stored,
+                });
+                continue;
+            }
+
+            reader.seek(SeekFrom::Start(offset))?;
+            let header = Header::from_reader(&mut reader)?;
+            if offset + HEADER_SIZE + header.compressed_size_file as u64 > file_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "entry exceeds end of file",
+                ));
+            }
+            entries.push(EntryInfo {
+                offset,
+                size: header.size_file,
+                compressed_size: header.compressed_size_file,
+                compress_method: header.compress_method,
+            });
+        }
+
+        Ok(Self {
+            reader,
+            entries,
+            file_len,
+        })
+    }
+
+    /// Number of entries in the archive.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn info(&self, index: usize) -> Option<&EntryInfo> {
+        self.entries.get(index)
+    }
+
+    pub fn infos(&self) -> &[EntryInfo] {
+        &self.entries
+    }
+
+    /// Loads and decompresses the entry at `index` into a newly allocated buffer.
+    pub fn entry(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        let info = *self.checked_info(index)?;
+        let mut buffer = vec![0; info.size];
+        read_block(&info, &mut self.reader, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Loads and decompresses the entry at `index` into `buffer`, returning the size of the
+    /// entry.
+    pub fn entry_into(&mut self, index: usize, buffer: &mut [u8]) -> io::Result<usize> {
+        let info = *self.checked_info(index)?;
+        read_block(&info, &mut self.reader, buffer)?;
+        Ok(info.size)
+    }
+
+    /// Returns the hidden entries stored after the entry at `index`, which end at the next entry
+    /// or at the end of the archive.
+    pub fn hidden_infos(&mut self, index: usize) -> io::Result<Vec<EntryInfo>> {
+        let info = *self.checked_info(index)?;
+        if info.is_blank() {
+            return Ok(Vec::new());
+        }
+        let end = self
+            .entries
+            .iter()
+            .map(|entry| entry.offset)
+            .filter(|&offset| offset > info.offset)
+            .min()
+            .unwrap_or(self.file_len);
+
+        let mut hidden = Vec::new();
+        let mut offset = info.offset + HEADER_SIZE + info.compressed_size as u64;
+        while offset < end {
+            self.reader.seek(SeekFrom::Start(offset))?;
+            let header = Header::from_reader(&mut self.reader)?;
+            hidden.push(EntryInfo {
+                offset,
+                size: header.size_file,
+                compressed_size: header.compressed_size_file,
+                compress_method: header.compress_method,
+            });
+            offset += HEADER_SIZE + header.compressed_size_file as u64;
+        }
+        if offset > end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "hidden entry exceeds the next entry",
+            ));
+        }
+        Ok(hidden)
+    }
+
+    /// Loads the hidden entry `n` stored after the entry at `index`.
+    ///
+    /// Hidden entries follow the data of an entry, each with its own header, and are not part of
+    /// the offset table. The VOX archives store the continued parts of a voice this way.
+    pub fn hidden_entry(&mut self, index: usize, n: usize) -> io::Result<Vec<u8>> {
+        let mut info = *self.checked_info(index)?;
+        if info.is_blank() {
+            return Err(io::Error::other("blank entries have no hidden entries"));
+        }
+        for _ in 0..=n {
+            let offset = info.offset + HEADER_SIZE + info.compressed_size as u64;
+            self.reader.seek(SeekFrom::Start(offset))?;
+            let header = Header::from_reader(&mut self.reader)?;
+            info = EntryInfo {
+                offset,
+                size: header.size_file,
+                compressed_size: header.compressed_size_file,
+                compress_method: header.compress_method,
+            };
+        }
+        let mut buffer = vec![0; info.size];
+        read_block(&info, &mut self.reader, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Loads the entry at `index` and decodes it as `T`.
+    pub fn get<T: Resource>(&mut self, index: usize) -> io::Result<T> {
+        decode(&self.entry(index)?)
+    }
+
+    fn checked_info(&self, index: usize) -> io::Result<&EntryInfo> {
+        self.entries
+            .get(index)
+            .ok_or_else(|| io::Error::other("out of bounds"))
+    }
+}
+
+/// Builds an HQR archive from a list of entries.
+///
+/// Each entry is either stored raw or compressed with the requested method. A compressed entry
+/// which does not get smaller than its data is stored raw instead, like the original tools do.
+#[derive(Debug, Default)]
+pub struct HqrWriter {
+    /// Data of every entry followed by its hidden entries, `None` for blank entries.
+    entries: Vec<Option<Vec<Block>>>,
+}
+
+/// Data of an entry with the requested compression.
+type Block = (Vec<u8>, CompressMethod);
+
+impl HqrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: impl Into<Vec<u8>>, compress_method: CompressMethod) {
+        self.entries
+            .push(Some(vec![(data.into(), compress_method)]));
+    }
+
+    /// Adds a hidden entry after the last entry, see [`HqrArchive::hidden_entry`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no entry yet or the last entry is blank.
+    pub fn push_hidden(&mut self, data: impl Into<Vec<u8>>, compress_method: CompressMethod) {
+        let Some(Some(blocks)) = self.entries.last_mut() else {
+            panic!("hidden entries must follow an entry with data");
+        };
+        blocks.push((data.into(), compress_method));
+    }
+
+    /// Adds an entry without data, which has a zero offset in the table.
+    pub fn push_blank(&mut self) {
+        self.entries.push(None);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path.as_ref())?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the offset table followed by every entry with its header.
+    ///
+    /// The first entry can not be blank, its offset is also the size of the table.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        if matches!(self.entries.first(), Some(None)) {
+            return Err(io::Error::other("the first entry can not be blank"));
+        }
 
-    read_block(&header, file, buffer)?;
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let blocks = entry.as_ref()?;
+                Some(
+                    blocks
+                        .iter()
+                        .map(|(data, compress_method)| compress_block(data, *compress_method))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
 
-    Ok(header.size_file)
+        // Note: the table has one more offset pointing to the end of the file.
+        let mut offset = (entries.len() + 1) * 4;
+        for blocks in &entries {
+            match blocks {
+                Some(blocks) => {
+                    writer.write_u32::<LittleEndian>(checked_u32(offset)?)?;
+                    for (_, _, block) in blocks {
+                        offset += HEADER_SIZE as usize + block.len();
+                    }
+                }
+                None => writer.write_u32::<LittleEndian>(0)?,
+            }
+        }
+        writer.write_u32::<LittleEndian>(checked_u32(offset)?)?;
+
+        for (size, compress_method, block) in entries.into_iter().flatten().flatten() {
+            let header = Header {
+                size_file: size,
+                compressed_size_file: block.len(),
+                compress_method,
+            };
+            header.to_writer(&mut writer)?;
+            writer.write_all(&block)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compresses `data` with `compress_method`, or keeps it raw if it does not get smaller. Returns
+/// the size of the data, the method used and the stored block.
+fn compress_block(
+    data: &[u8],
+    compress_method: CompressMethod,
+) -> (usize, CompressMethod, Vec<u8>) {
+    let compressed = match compress_method {
+        CompressMethod::Stored => None,
+        CompressMethod::Lzs | CompressMethod::Lzmit => {
+            Some(compress_lzs(data, compress_method.min_match_len()))
+                .filter(|compressed| compressed.len() < data.len())
+        }
+    };
+    match compressed {
+        Some(compressed) => (data.len(), compress_method, compressed),
+        None => (data.len(), CompressMethod::Stored, data.to_vec()),
+    }
+}
+
+fn checked_u32(value: usize) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("archive too large"))
+}
+
+pub fn load_hqr(path: impl AsRef<Path>, buffer: &mut [u8], index: usize) -> io::Result<usize> {
+    HqrArchive::open(path)?.entry_into(index, buffer)
 }
 
 pub fn load_hqrm(path: impl AsRef<Path>, index: usize) -> io::Result<Vec<u8>> {
-    let (file, header) = read_header(path, index)?;
-    let mut buffer = vec![0; header.size_file];
+    HqrArchive::open(path)?.entry(index)
+}
+
+pub fn load_hqrm_typed<R: Resource>(path: impl AsRef<Path>, index: usize) -> io::Result<R> {
+    HqrArchive::open(path)?.get(index)
+}
 
-    read_block(&header, file, &mut buffer)?;
+/// A type which can be decoded from the data of an HQR entry.
+pub trait Resource: Sized {
+    type Error: std::error::Error + Send + Sync + 'static;
 
-    Ok(buffer)
+    fn decode(data: &[u8]) -> Result<Self, Self::Error>;
 }
 
-pub fn load_hqrm_typed<R>(path: impl AsRef<Path>, index: usize) -> io::Result<R>
-where
-    R: TryFrom<Vec<u8>, Error = io::Error>,
-{
-    R::try_from(load_hqrm(path, index)?)
+/// Decodes `data` as `R`, turning a decoding error into an [`io::ErrorKind::InvalidData`] error.
+pub fn decode<R: Resource>(data: &[u8]) -> io::Result<R> {
+    R::decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Error returned when the data of an entry does not match the expected format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The entry does not have the expected size.
+    InvalidSize { expected: usize, actual: usize },
+    /// The entry is too short to contain the data described by its header or offset table.
+    Truncated,
+    /// The entry contains a value which is not valid for its format.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSize { expected, actual } => {
+                write!(f, "unexpected data size {}, expected {}", actual, expected)
+            }
+            Self::Truncated => write!(f, "data truncated"),
+            Self::Invalid(what) => write!(f, "invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    /// Reading past the end of the entry data is the only io error when decoding from a slice.
+    fn from(_: io::Error) -> Self {
+        Self::Truncated
+    }
+}
+
+/// Reads a table of `u32` offsets, which ends where the first offset points to.
+pub fn read_offset_table(data: &[u8]) -> Result<Vec<usize>, DecodeError> {
+    let mut reader = data;
+    let first = reader.read_u32::<LittleEndian>()? as usize;
+    if !first.is_multiple_of(4) || first > data.len() {
+        return Err(DecodeError::Invalid("offset table"));
+    }
+
+    let mut offsets = vec![first];
+    for _ in 1..first / 4 {
+        let offset = reader.read_u32::<LittleEndian>()? as usize;
+        if offset > data.len() {
+            return Err(DecodeError::Truncated);
+        }
+        offsets.push(offset);
+    }
+    Ok(offsets)
 }
 
 fn read_block(
-    header: &Header,
-    mut file: BufReader<File>,
+    info: &EntryInfo,
+    mut reader: impl Read + Seek,
     buffer: &mut [u8],
 ) -> Result<(), io::Error> {
-    if buffer.len() < header.size_file {
-        return Err(io::Error::new(io::ErrorKind::Other, "buffer too small"));
+    if buffer.len() < info.size {
+        return Err(io::Error::other("buffer too small"));
     }
+    if info.is_blank() {
+        return Ok(());
+    }
+
+    reader.seek(SeekFrom::Start(info.offset + HEADER_SIZE))?;
 
-    Ok(match header.compress_method {
+    match info.compress_method {
         CompressMethod::Stored => {
-            file.read_exact(&mut buffer[0..header.size_file])?;
+            reader.read_exact(&mut buffer[0..info.size])?;
         }
-        CompressMethod::Lzs => {
+        CompressMethod::Lzs | CompressMethod::Lzmit => {
             // Note: we don't use the extra decompression margin from the buffer, how it is done
             // in the original source code.
-            let mut compressed_buffer = vec![0; header.compressed_size_file];
-            file.read_exact(&mut compressed_buffer)?;
-            decompress_lzs(&compressed_buffer, &mut buffer[0..header.size_file]);
+            let mut compressed_buffer = vec![0; info.compressed_size];
+            reader.read_exact(&mut compressed_buffer)?;
+            decompress_lzs(
+                &compressed_buffer,
+                &mut buffer[0..info.size],
+                info.compress_method.min_match_len(),
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         }
-    })
-}
-
-fn read_header(
-    path: impl AsRef<Path>,
-    index: usize,
-) -> Result<(BufReader<File>, Header), io::Error> {
-    let mut file = BufReader::new(File::open(path.as_ref())?);
-
-    let num_blocks = file.read_u32::<LittleEndian>()? as usize / 4;
-    if num_blocks <= index {
-        return Err(io::Error::new(io::ErrorKind::Other, "out of bounds"));
     }
-
-    file.seek(SeekFrom::Start(index as u64 * 4))?;
-    let seek_index = file.read_u32::<LittleEndian>()?;
-
-    file.seek(SeekFrom::Start(seek_index.into()))?;
-    let header = Header::from_reader(&mut file)?;
-
-    Ok((file, header))
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -88,12 +460,19 @@ impl Header {
             compress_method: CompressMethod::from_int(reader.read_u16::<LittleEndian>()?)?,
         })
     }
+
+    fn to_writer(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_u32::<LittleEndian>(checked_u32(self.size_file)?)?;
+        writer.write_u32::<LittleEndian>(checked_u32(self.compressed_size_file)?)?;
+        writer.write_u16::<LittleEndian>(self.compress_method.to_int())
+    }
 }
 
-#[derive(Debug)]
-enum CompressMethod {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressMethod {
     Stored,
     Lzs,
+    Lzmit,
 }
 
 impl CompressMethod {
@@ -101,10 +480,134 @@ impl CompressMethod {
         match value {
             0 => Ok(Self::Stored),
             1 => Ok(Self::Lzs),
-            _ => Err(io::Error::new(
-                io::ErrorKind::Other,
-                "invalid compress method",
-            )),
+            2 => Ok(Self::Lzmit),
+            _ => Err(io::Error::other("invalid compress method")),
+        }
+    }
+
+    fn to_int(self) -> u16 {
+        match self {
+            Self::Stored => 0,
+            Self::Lzs => 1,
+            Self::Lzmit => 2,
+        }
+    }
+
+    /// Minimum length of a back-reference for the compressed methods.
+    fn min_match_len(self) -> usize {
+        match self {
+            Self::Stored | Self::Lzs => LZS_MIN_LEN,
+            Self::Lzmit => LZMIT_MIN_LEN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_entries() -> Vec<Vec<u8>> {
+        let mut pseudo_random = Vec::with_capacity(20000);
+        let mut state = 0x1234_5678u32;
+        for _ in 0..20000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            pseudo_random.push((state >> 16) as u8);
+        }
+        let pattern = (0..64000).map(|n| ((n / 7) % 13) as u8).collect();
+        vec![
+            Vec::new(),
+            vec![42],
+            vec![0; 5000],
+            b"Twinsen, the Dark Monk wants to see you. Twinsen, Twinsen!".to_vec(),
+            pattern,
+            pseudo_random,
+        ]
+    }
+
+    #[test]
+    fn test_hqr_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.hqr");
+
+        let entries = test_entries();
+        let methods = [
+            CompressMethod::Stored,
+            CompressMethod::Lzs,
+            CompressMethod::Lzmit,
+        ];
+        let mut writer = HqrWriter::new();
+        for method in methods {
+            for entry in &entries {
+                writer.push(entry.clone(), method);
+            }
+        }
+        writer.write(&path).unwrap();
+
+        let archive = HqrArchive::open(&path).unwrap();
+        assert_eq!(archive.len(), entries.len() * methods.len());
+        let compressed = archive
+            .infos()
+            .iter()
+            .filter(|info| info.compress_method != CompressMethod::Stored)
+            .count();
+        assert!(compressed > 0);
+
+        for (index, entry) in entries.iter().cycle().enumerate().take(archive.len()) {
+            assert_eq!(&load_hqrm(&path, index).unwrap(), entry, "entry {}", index);
         }
     }
+
+    #[test]
+    fn test_hqr_blank_entries() {
+        let mut writer = HqrWriter::new();
+        writer.push(vec![1, 2], CompressMethod::Stored);
+        writer.push_blank();
+        writer.push(vec![3], CompressMethod::Stored);
+        writer.push_blank();
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+
+        let mut archive = HqrArchive::from_reader(io::Cursor::new(data)).unwrap();
+        let blanks: Vec<_> = archive.infos().iter().map(EntryInfo::is_blank).collect();
+        assert_eq!(blanks, [false, true, false, true]);
+        assert_eq!(archive.entry(2).unwrap(), [3]);
+        assert!(archive.entry(3).unwrap().is_empty());
+
+        let mut writer = HqrWriter::new();
+        writer.push_blank();
+        assert!(writer.write_to(io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_hqr_hidden_entries() {
+        let mut writer = HqrWriter::new();
+        writer.push(vec![1; 50], CompressMethod::Lzs);
+        writer.push_hidden(vec![2; 50], CompressMethod::Lzs);
+        writer.push_hidden(vec![3, 4], CompressMethod::Stored);
+        writer.push(vec![5], CompressMethod::Stored);
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+
+        let mut archive = HqrArchive::from_reader(io::Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.entry(1).unwrap(), [5]);
+        assert_eq!(archive.hidden_entry(0, 0).unwrap(), [2; 50]);
+        assert_eq!(archive.hidden_entry(0, 1).unwrap(), [3, 4]);
+        // the entry following in the file is the next entry of the table
+        assert_eq!(archive.hidden_entry(0, 2).unwrap(), [5]);
+
+        let hidden = archive.hidden_infos(0).unwrap();
+        let methods: Vec<_> = hidden.iter().map(|info| info.compress_method).collect();
+        assert_eq!(methods, [CompressMethod::Lzs, CompressMethod::Stored]);
+        assert_eq!(hidden[1].size, 2);
+        assert!(archive.hidden_infos(1).unwrap().is_empty());
+
+        // hidden entries of the last entry end with the archive
+        writer.push_hidden(vec![6], CompressMethod::Stored);
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        let mut archive = HqrArchive::from_reader(io::Cursor::new(data)).unwrap();
+        assert_eq!(archive.hidden_infos(1).unwrap().len(), 1);
+        assert_eq!(archive.hidden_infos(0).unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,23 @@
+pub mod ambiance;
+pub mod backend;
+pub mod codepage;
+pub mod common;
+pub mod config;
+pub mod dialogue;
+pub mod font;
+pub mod gamemenu;
+pub mod global;
+pub mod headless_engine;
+pub mod hqr_ress;
+pub mod lib3d;
+pub mod libsys;
+pub mod message;
+pub mod plasma;
+pub mod playfla;
+pub mod resources;
+pub mod screen;
+pub mod screenshot;
+pub mod sdl_engine;
+pub mod sprite;
+pub mod translation;
+pub mod voc;
@@ -1,27 +1,77 @@
-pub fn decompress_lzs(src: &[u8], dst: &mut [u8]) {
-    let mut decompressed_len = dst.len();
+use std::fmt;
+
+/// Error returned when LZS compressed data is malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LzsError {
+    /// The compressed input ended before the output was complete.
+    TruncatedInput,
+    /// A back-reference points before the start of the output.
+    InvalidBackReference {
+        position: usize,
+        displacement: usize,
+    },
+}
+
+impl fmt::Display for LzsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TruncatedInput => write!(f, "truncated lzs input"),
+            Self::InvalidBackReference {
+                position,
+                displacement,
+            } => write!(
+                f,
+                "lzs back-reference at {} with displacement {} before start of output",
+                position, displacement
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LzsError {}
+
+/// Minimum match length of the LZS variant (HQR compression method 1).
+pub const LZS_MIN_LEN: usize = 2;
+/// Minimum match length of the LZMIT variant (HQR compression method 2).
+pub const LZMIT_MIN_LEN: usize = 3;
+
+/// Decompresses LZ77 style compressed data, where each back-reference copies at least `min_len`
+/// bytes.
+///
+/// Like the original, the last back-reference may be longer than the remaining output, the copy
+/// is cut at the end of `dst`.
+pub fn decompress_lzs(src: &[u8], dst: &mut [u8], min_len: usize) -> Result<(), LzsError> {
     let mut bits = 1; // bit checked in the mask
     let mut mask = 0; // mask for 8 compressed/non-compressed blocks
 
     let mut src_idx = 0;
     let mut dst_idx = 0;
 
-    while decompressed_len > 0 {
+    while dst_idx < dst.len() {
         if bits == 1 {
             // load mask byte
-            mask = src[src_idx];
+            mask = *src.get(src_idx).ok_or(LzsError::TruncatedInput)?;
             src_idx += 1;
         }
 
         if mask & bits == 0 {
             // compressed
-            let offset = u16::from_le_bytes([src[src_idx], src[src_idx + 1]]) as usize;
+            let bytes = src
+                .get(src_idx..src_idx + 2)
+                .ok_or(LzsError::TruncatedInput)?;
+            let offset = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
             src_idx += 2;
 
-            let len = (offset & 0xF) + 2;
+            let len = ((offset & 0xF) + min_len).min(dst.len() - dst_idx);
             let displacement = offset >> 4;
 
-            let data_idx = dst_idx - displacement - 1;
+            let data_idx =
+                dst_idx
+                    .checked_sub(displacement + 1)
+                    .ok_or(LzsError::InvalidBackReference {
+                        position: dst_idx,
+                        displacement,
+                    })?;
 
             if offset == 0 {
                 let data = dst[data_idx];
@@ -37,15 +87,137 @@ pub fn decompress_lzs(src: &[u8], dst: &mut [u8]) {
                 rhs[0..len].copy_from_slice(&lhs[data_idx..data_idx + len]);
             }
             dst_idx += len;
-            decompressed_len -= len;
         } else {
             // non-compressed
-            dst[dst_idx] = src[src_idx];
+            dst[dst_idx] = *src.get(src_idx).ok_or(LzsError::TruncatedInput)?;
             src_idx += 1;
             dst_idx += 1;
-            decompressed_len -= 1;
         }
 
         bits = bits.rotate_left(1);
     }
+
+    Ok(())
+}
+
+/// Size of the sliding window a back-reference can reach into.
+const LZS_WINDOW: usize = 4096;
+/// Number of candidate positions checked per byte when searching for a match.
+const LZS_MAX_CHAIN: usize = 256;
+
+/// Compresses `src` into the format read by [`decompress_lzs`] with the same `min_len`.
+///
+/// The match search is greedy and uses hash chains over the last [`LZS_WINDOW`] bytes.
+pub fn compress_lzs(src: &[u8], min_len: usize) -> Vec<u8> {
+    let max_len = 0xF + min_len;
+
+    let mut dst = Vec::with_capacity(src.len() + src.len() / 8 + 1);
+
+    // hash chains keyed by the next two bytes
+    let hash = |idx: usize| (usize::from(src[idx]) << 8) | usize::from(src[idx + 1]);
+    let mut head = vec![usize::MAX; 1 << 16];
+    let mut prev = vec![usize::MAX; src.len()];
+    let insert = |idx: usize, head: &mut [usize], prev: &mut [usize]| {
+        if idx + 1 < src.len() {
+            let h = hash(idx);
+            prev[idx] = head[h];
+            head[h] = idx;
+        }
+    };
+
+    let mut mask_idx = 0;
+    let mut bits = 0u8;
+    let mut src_idx = 0;
+
+    while src_idx < src.len() {
+        if bits == 0 {
+            mask_idx = dst.len();
+            dst.push(0);
+            bits = 1;
+        }
+
+        let max = max_len.min(src.len() - src_idx);
+        let mut best_len = 0;
+        let mut best_idx = 0;
+        if max >= min_len {
+            let mut candidate = head[hash(src_idx)];
+            let mut chain = 0;
+            while candidate != usize::MAX
+                && src_idx - candidate <= LZS_WINDOW
+                && chain < LZS_MAX_CHAIN
+            {
+                let len = (0..max)
+                    .take_while(|&n| src[candidate + n] == src[src_idx + n])
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_idx = candidate;
+                    if len == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_len >= min_len {
+            let displacement = src_idx - best_idx - 1;
+            let offset = (displacement << 4) | (best_len - min_len);
+            dst.extend_from_slice(&(offset as u16).to_le_bytes());
+            for idx in src_idx..src_idx + best_len {
+                insert(idx, &mut head, &mut prev);
+            }
+            src_idx += best_len;
+        } else {
+            dst[mask_idx] |= bits;
+            dst.push(src[src_idx]);
+            insert(src_idx, &mut head, &mut prev);
+            src_idx += 1;
+        }
+
+        bits = bits.wrapping_shl(1);
+    }
+
+    dst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_truncated_input() {
+        let mut dst = [0; 4];
+        assert_eq!(
+            decompress_lzs(&[], &mut dst, LZS_MIN_LEN),
+            Err(LzsError::TruncatedInput)
+        );
+        // a literal then a back-reference missing its second byte
+        assert_eq!(
+            decompress_lzs(&[0x01, b'a', 0x00], &mut dst, LZS_MIN_LEN),
+            Err(LzsError::TruncatedInput)
+        );
+    }
+
+    #[test]
+    fn test_decompress_invalid_back_reference() {
+        let mut dst = [0; 4];
+        // a literal then a back-reference 3 bytes back
+        assert_eq!(
+            decompress_lzs(&[0x01, b'a', 0x20, 0x00], &mut dst, LZS_MIN_LEN),
+            Err(LzsError::InvalidBackReference {
+                position: 1,
+                displacement: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_decompress_clamps_last_back_reference() {
+        // a literal then a back-reference of 17 bytes into an output of 5 bytes
+        let mut dst = [0; 5];
+        decompress_lzs(&[0x01, b'a', 0x0F, 0x00], &mut dst, LZS_MIN_LEN).unwrap();
+        assert_eq!(&dst, b"aaaaa");
+    }
 }
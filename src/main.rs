@@ -1,85 +1,230 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context as _};
-
-use crate::ambiance::{fade_to_black_pcx, fade_to_pal};
-use crate::gamemenu::{flip, ress_pict, timer_pause, Game};
-use crate::hqr_ress::{load_hqr, load_hqrm_typed};
-use crate::playfla::play_anim_fla;
-use crate::sdl_engine::SdlEngine;
-
-mod ambiance;
-mod common;
-mod gamemenu;
-mod global;
-mod hqr_ress;
-mod lib3d;
-mod libsys;
-mod message;
-mod playfla;
-mod screen;
-mod sdl_engine;
+use clap::Parser;
+use log::LevelFilter;
+
+use lba1_rs::ambiance::{fade_to_black_pcx, fade_to_pal};
+use lba1_rs::backend::Backend;
+use lba1_rs::common::{ImageId, RessId, NAME_HQR_RESS};
+use lba1_rs::config::{Config, Region};
+use lba1_rs::gamemenu::{flip, ress_pict, timer_esc, Game, MenuChoice, Wait};
+use lba1_rs::headless_engine::HeadlessEngine;
+use lba1_rs::message::{Language, NAME_HQR_TEXT};
+use lba1_rs::playfla::{play_anim_fla, FLA_DIR};
+use lba1_rs::screen::Image;
+use lba1_rs::sdl_engine::{SdlEngine, WindowOptions};
+
+/// Files of the game directory which are needed to start, the `fla` directory is only needed to
+/// play FLA movies.
+const REQUIRED_FILES: [&str; 2] = [NAME_HQR_RESS, NAME_HQR_TEXT];
+
+#[derive(Debug, Parser)]
+#[command(about = "Little Big Adventure")]
+struct Args {
+    /// Directory of the game files, the lba directory of the ADELINE CD
+    #[arg(long, value_name = "DIR", default_value = "cd/lba")]
+    data: PathBuf,
+    /// Language of the texts: EN, FR, DE, SP or IT, overrides the configuration file
+    #[arg(long)]
+    language: Option<Language>,
+    /// Go straight to the main menu
+    #[arg(long)]
+    skip_intro: bool,
+    /// Play the FLA movie with this name and exit
+    #[arg(long, value_name = "NAME")]
+    fla: Option<String>,
+    /// Size of a pixel of the game in the window
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    scale: u32,
+    /// Cover the whole screen
+    #[arg(long)]
+    fullscreen: bool,
+    /// Run without window nor sound, skipping the intro and quitting at the main menu
+    #[arg(long)]
+    headless: bool,
+    /// Most verbose messages shown: off, error, warn, info, debug or trace
+    #[arg(long, default_value = "warn")]
+    log_level: LevelFilter,
+}
 
 fn main() -> anyhow::Result<()> {
-    let root = assets_path()?;
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter_level(args.log_level)
+        .init();
+
+    let config = Config::load(&args.data).context("failed to read the configuration file")?;
+    let plays_intro = !args.skip_intro && !args.headless;
+    let plays_fla = config.movies.plays_fla() && (plays_intro || args.fla.is_some());
+    check_data(&args.data, plays_fla)?;
+
+    let engine: Box<dyn Backend> = if args.headless {
+        Box::new(HeadlessEngine::new())
+    } else {
+        let options = WindowOptions {
+            scale: args.scale,
+            fullscreen: args.fullscreen,
+        };
+        Box::new(SdlEngine::new(options).context("failed to init sdl engine")?)
+    };
 
-    let engine = SdlEngine::new().context("failed to init sdl engine")?;
+    let mut game = Game::new(args.data, engine);
+    game.set_config(config);
+    if let Some(language) = args.language {
+        game.message.set_language(language);
+    }
 
-    // TODO: read from setup.lst
-    const VERSION_US: bool = true;
+    if let Some(name) = args.fla {
+        play_anim_fla(&mut game, &name)?;
+        return Ok(());
+    }
 
-    let mut game = Game::new(root, engine);
+    if plays_intro && !intro(&mut game)? {
+        return Ok(());
+    }
+    game.global.palette = game.resources.ress(RessId::Pal)?;
+
+    // main game menu
+
+    let image: Image = game.resources.ress(RessId::MenuPcr)?;
+    game.screen.load_image(&image);
+    game.screen.copy_to(&mut game.log);
+    flip(&mut game);
+    fade_to_pal(
+        game.engine.as_mut(),
+        &game.global.palette,
+        &mut game.global.flag_black_pal,
+    );
+    if args.headless {
+        return Ok(());
+    }
+
+    loop {
+        match game.main_game_menu()? {
+            MenuChoice::Quit => return Ok(()),
+            MenuChoice::Options => {
+                if !game.game_option_menu()? {
+                    return Ok(());
+                }
+            }
+            choice => log::warn!("{:?} is not implemented yet", choice),
+        }
+    }
+}
+
+/// Logos and the movie shown before the main menu. Any key skips the current step, Esc the
+/// whole intro. Returns `false` if the window was closed.
+fn intro(game: &mut Game) -> anyhow::Result<bool> {
     game.adeline_logo()?;
 
-    fade_to_black_pcx(&mut game);
+    fade_to_black_pcx(game);
 
     // load different resources
-    game.global.palette = load_hqrm_typed(game.root.join("ress.hqr"), common::RESS_PAL)?;
+    game.global.palette = game.resources.ress(RessId::Pal)?;
 
     // bumper
-    if VERSION_US {
-        ress_pict(&mut game, common::RESS_BUMPER_PCR)?;
+    if game.config.region == Region::Us {
+        ress_pict(game, ImageId::BUMPER)?;
     } else {
-        ress_pict(&mut game, common::RESS_BUMPER2_PCR)?;
+        ress_pict(game, ImageId::BUMPER2)?;
     };
-    timer_pause(4);
-    fade_to_black_pcx(&mut game);
+    let wait = timer_esc(game, 4);
+    fade_to_black_pcx(game);
+    if wait != Wait::Done {
+        return Ok(wait != Wait::Quit);
+    }
 
     // logo EA
-    ress_pict(&mut game, common::RESS_BUMPER_EA_PCR)?;
-    timer_pause(2);
-    fade_to_black_pcx(&mut game);
+    ress_pict(game, ImageId::BUMPER_EA)?;
+    let wait = timer_esc(game, 2);
+    fade_to_black_pcx(game);
+    if wait != Wait::Done {
+        return Ok(wait != Wait::Quit);
+    }
 
     // FLA intro
-    play_anim_fla(&mut game, "dragon3")?;
-
-    // main game menu
-
-    load_hqr(
-        game.root.join("ress.hqr"),
-        &mut game.screen.data,
-        common::RESS_MENU_PCR,
-    )?;
-    game.screen.copy_to(&mut game.log);
-    flip(&mut game);
-    fade_to_pal(
-        &mut game.engine,
-        &game.global.palette,
-        &mut game.global.flag_black_pal,
-    );
+    let wait = play_anim_fla(game, "dragon3")?;
+    Ok(wait != Wait::Quit)
+}
 
-    game.main_game_menu()
+/// Fails with the list of the required files missing from the game directory `root`, which
+/// include the `fla` directory if `fla` movies are played.
+fn check_data(root: &Path, fla: bool) -> anyhow::Result<()> {
+    let mut missing: Vec<_> = REQUIRED_FILES
+        .into_iter()
+        .filter(|name| !root.join(name).exists())
+        .map(str::to_owned)
+        .collect();
+    if fla && !root.join(FLA_DIR).is_dir() {
+        missing.push(format!("{}/", FLA_DIR));
+    }
+    if !missing.is_empty() {
+        bail!(
+            "missing game files in {}: {}\nuse --data to set the directory of the game files",
+            root.display(),
+            missing.join(", ")
+        );
+    }
+    Ok(())
 }
 
-fn assets_path() -> anyhow::Result<PathBuf> {
-    let cd_root = if let Some(path) = std::env::args().nth(1) {
-        PathBuf::from(path)
-    } else {
-        let local_cd = PathBuf::from("cd");
-        if !local_cd.exists() || !local_cd.is_dir() {
-            bail!("please provide a path to the ADELINE CD");
-        }
-        local_cd
-    };
-    Ok(cd_root.join("lba"))
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let args = Args::try_parse_from(["lba1"]).unwrap();
+        assert_eq!(args.data, Path::new("cd/lba"));
+        assert_eq!(args.language, None);
+        assert_eq!(args.scale, 1);
+        assert_eq!(args.log_level, LevelFilter::Warn);
+        assert!(!args.skip_intro && !args.headless && !args.fullscreen);
+
+        let args = Args::try_parse_from([
+            "lba1",
+            "--data",
+            "game",
+            "--language",
+            "fr",
+            "--scale",
+            "3",
+            "--fla",
+            "dragon3",
+            "--headless",
+            "--log-level",
+            "debug",
+        ])
+        .unwrap();
+        assert_eq!(args.data, Path::new("game"));
+        assert_eq!(args.language, Some(Language::French));
+        assert_eq!(args.scale, 3);
+        assert_eq!(args.fla.as_deref(), Some("dragon3"));
+        assert!(args.headless);
+        assert_eq!(args.log_level, LevelFilter::Debug);
+
+        assert!(Args::try_parse_from(["lba1", "--scale", "0"]).is_err());
+        assert!(Args::try_parse_from(["lba1", "--scale", "9"]).is_err());
+        assert!(Args::try_parse_from(["lba1", "--language", "xx"]).is_err());
+    }
+
+    #[test]
+    fn test_check_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let error = check_data(root, false).unwrap_err().to_string();
+        assert!(error.contains("ress.hqr, text.hqr\n"), "{}", error);
+
+        fs::write(root.join(NAME_HQR_RESS), []).unwrap();
+        fs::write(root.join(NAME_HQR_TEXT), []).unwrap();
+        check_data(root, false).unwrap();
+        let error = check_data(root, true).unwrap_err().to_string();
+        assert!(error.contains(": fla/\n"), "{}", error);
+
+        fs::create_dir(root.join(FLA_DIR)).unwrap();
+        check_data(root, true).unwrap();
+    }
 }
@@ -1,64 +1,570 @@
+use std::fmt;
 use std::io;
-use std::path::PathBuf;
+use std::str::FromStr;
 
-use crate::hqr_ress::load_hqr;
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::codepage;
+use crate::gamemenu::{poll_key, Game, Wait};
+use crate::global::VolumeChannel;
+use crate::hqr_ress::{DecodeError, Resource};
+use crate::resources::Resources;
+use crate::sdl_engine::delay_ms;
+use crate::voc::Sample;
 
 #[derive(Debug)]
 pub struct Message {
-    root: PathBuf,
     last_file_init: Option<usize>,
-    language: usize,
+    language: Language,
 
-    max_text: usize,
     flag_speak: bool,
 
-    buffer_text: [u8; 25000],
-    buffer_order: [u8; 1024],
+    /// Texts of the current bank by id, decoded from the game codepage.
+    texts: Vec<(u16, String)>,
+}
+
+/// Languages of the texts shipped in `text.hqr`, in the order of their banks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+    German,
+    Spanish,
+    Italian,
 }
 
-// const LIST_LANGUAGE: [&str; 5] = ["EN_", "FR_", "DE_", "SP_", "IT_"];
+impl Language {
+    pub const ALL: [Language; 5] = [
+        Self::English,
+        Self::French,
+        Self::German,
+        Self::Spanish,
+        Self::Italian,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Two letter code of the language, as used in the names of the voice files.
+    ///
+    /// Original: `ListLanguage`
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "EN",
+            Self::French => "FR",
+            Self::German => "DE",
+            Self::Spanish => "SP",
+            Self::Italian => "IT",
+        }
+    }
+
+    /// ISO 639-1 code of the language.
+    pub fn iso_code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::French => "fr",
+            Self::German => "de",
+            Self::Spanish => "es",
+            Self::Italian => "it",
+        }
+    }
 
-// const LIST_FILE_TEXT: [&str; 15] = [
-//     "sys", "cre", "gam", "000", "001", "002", "003", "004", "005", "006", "007", "008", "009",
-//     "010", "011",
-// ];
+    /// Name of the language in `lba.cfg`, as written by the original setup.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::French => "Francais",
+            Self::German => "Deutsch",
+            Self::Spanish => "Espanol",
+            Self::Italian => "Italiano",
+        }
+    }
+}
 
-const NAME_HQR_TEXT: &str = "text.hqr";
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
 
-const MAX_TEXT_LANG: usize = 14;
+impl FromStr for Language {
+    type Err = String;
+
+    /// Parses the two letter code, the ISO 639-1 code, the english name or the name in `lba.cfg`
+    /// of the language, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|language| {
+                s.eq_ignore_ascii_case(language.code())
+                    || s.eq_ignore_ascii_case(language.iso_code())
+                    || s.eq_ignore_ascii_case(&format!("{:?}", language))
+                    || s.eq_ignore_ascii_case(language.config_name())
+            })
+            .ok_or_else(|| {
+                let codes: Vec<_> = Self::ALL.iter().map(|language| language.code()).collect();
+                format!(
+                    "unknown language {:?}, expected one of {}",
+                    s,
+                    codes.join(", ")
+                )
+            })
+    }
+}
+
+/// Names of the text banks, used for the VOX archive of each bank.
+const LIST_FILE_TEXT: [&str; 15] = [
+    "sys", "cre", "gam", "000", "001", "002", "003", "004", "005", "006", "007", "008", "009",
+    "010", "011",
+];
+
+pub const NAME_HQR_TEXT: &str = "text.hqr";
+
+/// Number of text banks of every language in `text.hqr`.
+pub const MAX_TEXT_LANG: usize = 14;
+
+/// Name of the text bank `file_index`.
+pub fn bank_name(file_index: usize) -> Option<&'static str> {
+    LIST_FILE_TEXT[..MAX_TEXT_LANG].get(file_index).copied()
+}
+
+/// Index of the order table of the bank `file_index` in `text.hqr`, the texts follow it.
+pub fn bank_index(language: Language, file_index: usize) -> usize {
+    language.index() * MAX_TEXT_LANG * 2 + file_index * 2
+}
+
+/// Decodes the texts of a bank with their ids, in the order of the bank.
+pub fn bank_texts(order: &TextOrder, bank: &TextBank) -> Vec<(u16, String)> {
+    order
+        .ids
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &id)| Some((id, codepage::decode(bank.get(index)?))))
+        .collect()
+}
+
+impl Default for Message {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Message {
-    pub fn new(root: PathBuf) -> Self {
+    pub fn new() -> Self {
         Self {
-            root,
             last_file_init: None,
-            language: 1, // English
-            max_text: 0,
+            language: Language::English,
             flag_speak: false,
-            buffer_text: [0; 25000],
-            buffer_order: [0; 1024],
+            texts: Vec::new(),
         }
     }
 
-    pub fn init_dial(&mut self, file_index: usize) -> io::Result<()> {
-        let last_file_index = self.last_file_init.replace(file_index);
-        if last_file_index == Some(file_index) {
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Changes the language of the texts, the current bank is reloaded by the next
+    /// [`Message::init_dial`].
+    pub fn set_language(&mut self, language: Language) {
+        if language != self.language {
+            self.language = language;
+            self.last_file_init = None;
+        }
+    }
+
+    /// Loads the text bank `file_index` of the current language. Banks missing for the current
+    /// language are loaded in English instead.
+    pub fn init_dial(&mut self, resources: &mut Resources, file_index: usize) -> io::Result<()> {
+        if self.last_file_init == Some(file_index) {
             return Ok(());
         }
 
-        let path = self.root.join(NAME_HQR_TEXT);
+        let (order, bank) = match load_bank(resources, self.language, file_index)? {
+            Some(texts) => texts,
+            None if self.language != Language::English => {
+                log::warn!(
+                    "text bank {} missing for language {}, falling back to English",
+                    file_index,
+                    self.language
+                );
+                load_bank(resources, Language::English, file_index)?
+                    .ok_or_else(|| missing_bank(file_index))?
+            }
+            None => return Err(missing_bank(file_index)),
+        };
+        self.set_texts(&order, &bank);
+        self.last_file_init = Some(file_index);
+
+        Ok(())
+    }
+
+    pub fn flag_speak(&self) -> bool {
+        self.flag_speak
+    }
+
+    /// Enables or disables the voices.
+    pub fn set_speak(&mut self, flag_speak: bool) {
+        self.flag_speak = flag_speak;
+    }
+
+    /// Loads the voice of the text `id` of the current bank from its VOX archive, with all its
+    /// parts joined. Returns `None` if the text is not part of the current bank.
+    pub fn voice(&mut self, resources: &mut Resources, id: u16) -> io::Result<Option<Sample>> {
+        let Some(file_index) = self.last_file_init else {
+            return Ok(None);
+        };
+        let Some(position) = self.texts.iter().position(|(text_id, _)| *text_id == id) else {
+            return Ok(None);
+        };
+        let name = voice_file_name(self.language, file_index)?;
+        load_voice(resources, &name, position).map(Some)
+    }
+
+    /// Returns the text `id` of the current bank.
+    ///
+    /// Original: `GetMultiText`
+    pub fn get_text(&self, id: u16) -> Option<&str> {
+        self.texts
+            .iter()
+            .find(|(text_id, _)| *text_id == id)
+            .map(|(_, text)| text.as_str())
+    }
+
+    fn set_texts(&mut self, order: &TextOrder, bank: &TextBank) {
+        self.texts = bank_texts(order, bank);
+    }
+}
 
-        let index = (self.language * MAX_TEXT_LANG * 2 + file_index * 2) / 2;
-        let max_text = load_hqr(&path, &mut self.buffer_order, index)?;
-        self.max_text = max_text;
+/// Loads the bank `file_index` of `language`, or `None` if the archive has no such bank.
+fn load_bank(
+    resources: &mut Resources,
+    language: Language,
+    file_index: usize,
+) -> io::Result<Option<(TextOrder, TextBank)>> {
+    let index = bank_index(language, file_index);
+    let archive = resources.archive(NAME_HQR_TEXT)?;
+    let exists = |index| matches!(archive.info(index), Some(info) if !info.is_blank());
+    if !exists(index) || !exists(index + 1) {
+        return Ok(None);
+    }
+    let order = resources.get(NAME_HQR_TEXT, index)?;
+    let bank = resources.get(NAME_HQR_TEXT, index + 1)?;
+    Ok(Some((order, bank)))
+}
 
-        let index = self.language * MAX_TEXT_LANG * 2 + file_index + 1;
-        load_hqr(path, &mut self.buffer_text, index)?;
+fn missing_bank(file_index: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("text bank {} missing from {}", file_index, NAME_HQR_TEXT),
+    )
+}
 
-        if self.flag_speak {
-            // self.init_speak(file_index)?;
+/// Name of the VOX archive of the bank `file_index` in `language`.
+fn voice_file_name(language: Language, file_index: usize) -> io::Result<String> {
+    let file = LIST_FILE_TEXT.get(file_index).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no voices for text bank {}", file_index),
+        )
+    })?;
+    Ok(format!(
+        "vox/{}_{}.vox",
+        language.code().to_ascii_lowercase(),
+        file
+    ))
+}
+
+/// Loads the voice of entry `index` of the archive `name` with its continued parts, which are
+/// the hidden entries stored after it.
+fn load_voice(resources: &mut Resources, name: &str, index: usize) -> io::Result<Sample> {
+    let mut sample: Sample = resources.get(name, index)?;
+    let mut part = 0;
+    while sample.continued {
+        let next = resources.hidden(name, index, part)?;
+        sample
+            .append(next)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        part += 1;
+    }
+    Ok(sample)
+}
+
+/// Starts the voice of the text `id` if the voices are enabled. Returns whether a voice is
+/// played.
+///
+/// Original: `Speak`
+pub fn speak(game: &mut Game, id: u16) -> anyhow::Result<bool> {
+    if !game.message.flag_speak() {
+        return Ok(false);
+    }
+    let sample = match game.message.voice(&mut game.resources, id) {
+        Ok(Some(sample)) => sample,
+        Ok(None) => return Ok(false),
+        // installs without voices have no VOX archives
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            log::info!("no voice for text {}: {}", id, e);
+            return Ok(false);
         }
+        Err(e) => return Err(e.into()),
+    };
+    let volume = game.global.mixed_volume(VolumeChannel::Sample);
+    game.engine.play_sample(&sample, volume);
+    Ok(true)
+}
 
-        Ok(())
+/// Waits for the voice to end. A key press or closing the window stops it, in which case how it
+/// was stopped is returned.
+///
+/// Original: `WaitEndSpeak`
+pub fn wait_speak(game: &mut Game) -> Option<Wait> {
+    while game.engine.is_sample_playing() {
+        if let Some(wait) = poll_key(game) {
+            game.engine.stop_sample();
+            return Some(wait);
+        }
+        delay_ms(10);
+    }
+    None
+}
+
+/// Texts of one bank of `text.hqr`, stored behind a table of `u16` offsets.
+#[derive(Debug, Clone, Default)]
+pub struct TextBank {
+    offsets: Vec<usize>,
+    data: Vec<u8>,
+}
+
+impl TextBank {
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Builds a bank of the raw `texts`. Returns `None` if the bank does not fit the `u16`
+    /// offsets.
+    pub fn from_texts<T: AsRef<[u8]>>(texts: &[T]) -> Option<Self> {
+        let mut offsets = vec![(texts.len() + 1) * 2];
+        for text in texts {
+            offsets.push(offsets.last()? + text.as_ref().len() + 1);
+        }
+        if *offsets.last()? > usize::from(u16::MAX) {
+            return None;
+        }
+
+        let mut data = Vec::with_capacity(*offsets.last()?);
+        for &offset in &offsets {
+            data.extend_from_slice(&(offset as u16).to_le_bytes());
+        }
+        for text in texts {
+            data.extend_from_slice(text.as_ref());
+            data.push(0);
+        }
+        Some(Self { offsets, data })
+    }
+
+    /// The bank as stored in `text.hqr`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Raw bytes of the text at position `index` in the bank, without the terminating nul.
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let text = &self.data[*self.offsets.get(index)?..*self.offsets.get(index + 1)?];
+        Some(text.strip_suffix(&[0]).unwrap_or(text))
+    }
+}
+
+impl Resource for TextBank {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = data;
+        let first = reader.read_u16::<LittleEndian>()? as usize;
+        if !first.is_multiple_of(2) || first > data.len() {
+            return Err(DecodeError::Invalid("text offset table"));
+        }
+
+        let mut offsets = vec![first];
+        for _ in 1..first / 2 {
+            let offset = reader.read_u16::<LittleEndian>()? as usize;
+            if offset < *offsets.last().unwrap() || offset > data.len() {
+                return Err(DecodeError::Invalid("text offset"));
+            }
+            offsets.push(offset);
+        }
+
+        Ok(Self {
+            offsets,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Text ids of one bank of `text.hqr`, in the order of the texts in the matching [`TextBank`].
+#[derive(Debug, Clone, Default)]
+pub struct TextOrder {
+    pub ids: Vec<u16>,
+}
+
+impl TextOrder {
+    /// The order table as stored in `text.hqr`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.ids.iter().flat_map(|id| id.to_le_bytes()).collect()
+    }
+}
+
+impl Resource for TextOrder {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        if !data.len().is_multiple_of(2) {
+            return Err(DecodeError::Invalid("text order table"));
+        }
+        let ids = data
+            .chunks_exact(2)
+            .map(|id| u16::from_le_bytes([id[0], id[1]]))
+            .collect();
+        Ok(Self { ids })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless_engine::HeadlessEngine;
+    use crate::hqr_ress::{CompressMethod, HqrWriter};
+    use crate::voc::tests::voc;
+
+    #[test]
+    fn test_get_text() {
+        let mut data = vec![6, 0, 14, 0, 25, 0];
+        data.extend_from_slice(b"Options\0Param\x8atres\0");
+        let bank = TextBank::decode(&data).unwrap();
+        assert_eq!(bank.len(), 2);
+
+        let order = TextOrder::decode(&[23, 0, 20, 0]).unwrap();
+        let mut message = Message::new();
+        message.set_texts(&order, &bank);
+
+        assert_eq!(message.get_text(23), Some("Options"));
+        assert_eq!(message.get_text(20), Some("Paramètres"));
+        assert_eq!(message.get_text(21), None);
+    }
+
+    #[test]
+    fn test_voice_with_continued_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("vox")).unwrap();
+        // the first voice continues in two hidden entries
+        let mut writer = HqrWriter::new();
+        writer.push(voc(1, 166, &[1, 2]), CompressMethod::Stored);
+        writer.push_hidden(voc(1, 166, &[3]), CompressMethod::Lzs);
+        writer.push_hidden(voc(b'C', 166, &[4]), CompressMethod::Stored);
+        writer.push(voc(b'C', 166, &[5]), CompressMethod::Lzs);
+        writer.write(root.join("vox/en_sys.vox")).unwrap();
+
+        let mut resources = Resources::new(root);
+        let mut message = Message::new();
+        message.set_texts(
+            &TextOrder { ids: vec![7, 9] },
+            &TextBank::decode(&[6, 0, 7, 0, 8, 0, 0, 0]).unwrap(),
+        );
+        message.last_file_init = Some(0);
+
+        let sample = message.voice(&mut resources, 9).unwrap().unwrap();
+        assert_eq!(sample.data, [5]);
+        let sample = message.voice(&mut resources, 7).unwrap().unwrap();
+        assert_eq!(sample.data, [1, 2, 3, 4]);
+        assert!(!sample.continued);
+        assert!(message.voice(&mut resources, 8).unwrap().is_none());
+
+        // banks without voices
+        message.last_file_init = Some(LIST_FILE_TEXT.len());
+        assert!(message.voice(&mut resources, 7).is_err());
+    }
+
+    #[test]
+    fn test_speak() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let engine = HeadlessEngine::new();
+        let state = engine.state();
+        let mut game = Game::new(root, Box::new(engine));
+        game.message.set_texts(
+            &TextOrder { ids: vec![20] },
+            &TextBank::from_texts(&["New game"]).unwrap(),
+        );
+        game.message.last_file_init = Some(0);
+        game.message.set_speak(true);
+
+        // installs without voices
+        assert!(!speak(&mut game, 20).unwrap());
+
+        std::fs::create_dir_all(root.join("vox")).unwrap();
+        let mut writer = HqrWriter::new();
+        writer.push(voc(b'C', 166, &[1, 2]), CompressMethod::Stored);
+        writer.write(root.join("vox/en_sys.vox")).unwrap();
+        game.global.master_volume = 128;
+        game.global.sample_volume = 100;
+        assert!(speak(&mut game, 20).unwrap());
+        let (sample, volume) = state.borrow_mut().samples.pop().unwrap();
+        assert_eq!(sample.data, [1, 2]);
+        assert_eq!(volume, 100 * 128 / 255);
+        assert_eq!(wait_speak(&mut game), None);
+    }
+
+    #[test]
+    fn test_init_dial_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let order = TextOrder { ids: vec![20] };
+        let bank = TextBank::from_texts(&["New game"]).unwrap();
+        let mut writer = HqrWriter::new();
+        for language in [Language::English, Language::French, Language::German] {
+            while writer.len() < bank_index(language, 0) {
+                writer.push_blank();
+            }
+            if language == Language::German {
+                // corrupted bank
+                writer.push(order.to_bytes(), CompressMethod::Stored);
+                writer.push(vec![0xFF], CompressMethod::Stored);
+            } else if language == Language::English {
+                writer.push(order.to_bytes(), CompressMethod::Stored);
+                writer.push(bank.as_bytes().to_vec(), CompressMethod::Stored);
+            }
+        }
+        writer.write(dir.path().join(NAME_HQR_TEXT)).unwrap();
+        let mut resources = Resources::new(dir.path());
+
+        // French is missing and falls back to English
+        let mut message = Message::new();
+        message.set_language(Language::French);
+        message.init_dial(&mut resources, 0).unwrap();
+        assert_eq!(message.get_text(20), Some("New game"));
+
+        // errors other than a missing bank are returned
+        message.set_language(Language::German);
+        let e = message.init_dial(&mut resources, 0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        message.set_language(Language::English);
+        let e = message.init_dial(&mut resources, 1).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!("fr".parse(), Ok(Language::French));
+        assert_eq!("German".parse(), Ok(Language::German));
+        assert_eq!("IT".parse(), Ok(Language::Italian));
+        assert_eq!("Espanol".parse(), Ok(Language::Spanish));
+        assert_eq!("es".parse(), Ok(Language::Spanish));
+        assert_eq!("deutsch".parse(), Ok(Language::German));
+        assert!("xx".parse::<Language>().is_err());
     }
 }
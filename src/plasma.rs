@@ -0,0 +1,132 @@
+use crate::hqr_ress::{DecodeError, Resource};
+use crate::screen::{Rect, Screen, WIDTH};
+
+pub const PLASMA_WIDTH: usize = 320;
+pub const PLASMA_HEIGHT: usize = 50;
+
+/// First plasma line which is shown; the lines above are where the flames die out.
+const FIRST_VISIBLE_LINE: usize = 5;
+
+/// Animated fire filling the selected menu button and the volume bars.
+///
+/// The state is a double buffer of `PLASMA_WIDTH` x `PLASMA_HEIGHT` intensities, initialized from
+/// the `RessId::InitPlasma` entry of `ress.hqr`. Every pixel of the plasma covers 2x2 pixels of the
+/// screen.
+#[derive(Debug, Clone)]
+pub struct Plasma {
+    buffer: Box<[u8]>,
+}
+
+impl Default for Plasma {
+    fn default() -> Self {
+        Self {
+            buffer: vec![0; 2 * PLASMA_WIDTH * PLASMA_HEIGHT].into(),
+        }
+    }
+}
+
+impl Resource for Plasma {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let len = 2 * PLASMA_WIDTH * PLASMA_HEIGHT;
+        let data = data.get(..len).ok_or(DecodeError::InvalidSize {
+            expected: len,
+            actual: data.len(),
+        })?;
+        Ok(Self {
+            buffer: data.into(),
+        })
+    }
+}
+
+impl Plasma {
+    /// Advances the fire by one frame: every pixel becomes the average of its neighbours, fades
+    /// out statistically and the flames scroll up by one line.
+    pub fn update(&mut self) {
+        const W: usize = PLASMA_WIDTH;
+        const H: usize = PLASMA_HEIGHT;
+
+        let (front, back) = self.buffer.split_at_mut(W * H);
+        for j in 1..H - 1 {
+            for i in 1..W - 1 {
+                let sum: u16 = [
+                    (j - 1) * W + i - 1,
+                    (j - 1) * W + i,
+                    (j - 1) * W + i + 1,
+                    j * W + i - 1,
+                    j * W + i + 1,
+                    (j + 1) * W + i - 1,
+                    (j + 1) * W + i,
+                    (j + 1) * W + i + 1,
+                ]
+                .iter()
+                .map(|&idx| u16::from(front[idx]))
+                .sum();
+
+                // the 2 least significant bits are used to fade the flames statistically
+                let mut c = (sum >> 3) | ((sum & 0x0003) << 13);
+                if c & 0x6500 == 0 && (j >= H - 4 || c > 0) {
+                    c = c.wrapping_sub(1);
+                }
+
+                back[j * W + i] = c as u8;
+            }
+        }
+
+        // flip the double buffer while scrolling the fire one line up
+        front[..(H - 1) * W].copy_from_slice(&back[W..H * W]);
+    }
+
+    /// Draws the fire into `rect` of `screen` with the 16 color ramp starting at `color`.
+    pub fn draw(&self, screen: &mut Screen, rect: Rect, color: u8) {
+        let Some(clipped) = rect.intersect(&screen.clip()) else {
+            return;
+        };
+        let max_color = color.saturating_add(15);
+
+        for y in clipped.y0..=clipped.y1 {
+            let line = (FIRST_VISIBLE_LINE + (y - rect.y0) as usize / 2).min(PLASMA_HEIGHT - 1);
+            for x in clipped.x0..=clipped.x1 {
+                let column = ((x - rect.x0) as usize / 2).min(PLASMA_WIDTH - 1);
+                let intensity = self.buffer[line * PLASMA_WIDTH + column];
+                screen.data[y as usize * WIDTH + x as usize] =
+                    (intensity / 2).saturating_add(color).min(max_color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plasma_draw_clipped() {
+        let mut plasma = Plasma::default();
+        plasma.buffer[FIRST_VISIBLE_LINE * PLASMA_WIDTH..][..2].copy_from_slice(&[10, 255]);
+
+        let mut screen = Screen::default();
+        screen.set_clip(Rect::new(0, 0, 2, 0));
+        plasma.draw(&mut screen, Rect::new(0, 0, 3, 1), 0x40);
+
+        assert_eq!(screen.data[..4], [0x45, 0x45, 0x4F, 0]);
+        assert!(screen.data[WIDTH..WIDTH + 4].iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_plasma_update() {
+        // the bottom lines wrap around and feed the flames
+        let mut plasma = Plasma::default();
+        plasma.update();
+        assert_eq!(plasma.buffer[(PLASMA_HEIGHT - 6) * PLASMA_WIDTH + 1], 0);
+        assert_eq!(plasma.buffer[(PLASMA_HEIGHT - 5) * PLASMA_WIDTH + 1], 255);
+
+        plasma.buffer[..PLASMA_WIDTH * PLASMA_HEIGHT].fill(200);
+        plasma.update();
+        let front = &plasma.buffer[..PLASMA_WIDTH * PLASMA_HEIGHT];
+        assert!(front[PLASMA_WIDTH + 1..2 * PLASMA_WIDTH - 1]
+            .iter()
+            .all(|&c| c == 199));
+    }
+}
@@ -7,16 +7,13 @@ use std::time::{Duration, Instant};
 use anyhow::Context;
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use crate::ambiance::{
-    fade_to_black, fade_to_black_pcx, fade_to_pal, fade_to_pal_pcx, set_black_pal, Palette,
-};
-use crate::common::RESS_FLA_PCX;
-use crate::gamemenu::{clear, flip, timer_esc, Game};
-use crate::hqr_ress::load_hqrm;
+use crate::ambiance::{fade_to_black, fade_to_pal, set_black_pal, Palette};
+use crate::config::MovieMode;
+use crate::gamemenu::{clear, flip, poll_key, Game, Wait};
 use crate::sdl_engine::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
-const FLA_FROM_CD: bool = true;
-const FLA_DIR: &str = "fla";
+/// Directory of the FLA movies in the game directory.
+pub const FLA_DIR: &str = "fla";
 const FLA_EXT: &str = "fla";
 
 const FLA_WIDTH: usize = 320;
@@ -24,15 +21,21 @@ const FLA_HEIGHT: usize = 200;
 
 const VERSION: &str = "V1.3";
 
-pub fn play_anim_fla(game: &mut Game, name: &str) -> anyhow::Result<()> {
-    if !FLA_FROM_CD {
-        play_disk_fla(game, name)?;
-        return Ok(());
+/// Plays the movie `name`, any key stops it. Returns how it ended.
+pub fn play_anim_fla(game: &mut Game, name: &str) -> anyhow::Result<Wait> {
+    let path = game.root.join(FLA_DIR).join(name).with_extension(FLA_EXT);
+    match game.config.movies {
+        MovieMode::None => return Ok(Wait::Done),
+        // the PCX slideshows are not implemented, the FLA movie is played instead if installed
+        MovieMode::FlaPcx if !path.exists() => {
+            log::warn!("PCX slideshows are not supported, skipping movie {}", name);
+            return Ok(Wait::Done);
+        }
+        MovieMode::Fla | MovieMode::FlaWide | MovieMode::FlaPcx => {}
     }
 
     // StopMusicCD
 
-    let path = game.root.join(FLA_DIR).join(name).with_extension(FLA_EXT);
     let mut reader = BufReader::new(
         File::open(&path).context(format!("failed to open fla movie at {}", path.display()))?,
     );
@@ -49,7 +52,14 @@ pub fn play_anim_fla(game: &mut Game, name: &str) -> anyhow::Result<()> {
         let frame_duration = Duration::from_millis(1000 / game.fla.header.cadence_animation as u64);
 
         for _ in 0..game.fla.header.num_frames {
-            // exit on ESC
+            if let Some(wait) = poll_key(game) {
+                fade_to_black(
+                    game.engine.as_mut(),
+                    &game.fla.palette,
+                    &mut game.global.flag_black_pal,
+                );
+                return Ok(wait);
+            }
             let now = Instant::now();
 
             draw_next_frame_fla(game, &mut reader)?;
@@ -62,7 +72,7 @@ pub fn play_anim_fla(game: &mut Game, name: &str) -> anyhow::Result<()> {
         }
     }
 
-    Ok(())
+    Ok(Wait::Done)
 }
 
 fn upscale_mcga(game: &mut Game) {
@@ -94,7 +104,7 @@ fn upscale_mcga(game: &mut Game) {
 fn management_palette(game: &mut Game) {
     if game.fla.flag_first {
         fade_to_pal(
-            &mut game.engine,
+            game.engine.as_mut(),
             &game.fla.palette,
             &mut game.global.flag_black_pal,
         );
@@ -168,7 +178,7 @@ fn draw_next_frame_fla(game: &mut Game, reader: impl Read) -> io::Result<()> {
                     }
                     2 => {
                         fade_to_black(
-                            &mut game.engine,
+                            game.engine.as_mut(),
                             &fla.palette,
                             &mut game.global.flag_black_pal,
                         );
@@ -237,49 +247,6 @@ fn init_fla(mut reader: impl Read, fla: &mut Fla) -> io::Result<()> {
     Ok(())
 }
 
-fn play_disk_fla(game: &mut Game, name: &str) -> io::Result<()> {
-    let txt = load_hqrm(game.root.join("ress.hqr"), RESS_FLA_PCX)?;
-
-    let (name, _) = name.split_once('.').unwrap();
-
-    let txt = std::str::from_utf8(&txt).unwrap();
-
-    let indexes = search_fla(name, txt);
-    for index in indexes {
-        match index {
-            200 => {}
-            201 => {}
-            202 => {}
-            _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "todo")),
-        }
-
-        flip(game);
-        fade_to_pal_pcx(game);
-
-        timer_esc(4);
-
-        // TODO: return on escape
-
-        fade_to_black_pcx(game);
-    }
-
-    Ok(())
-}
-
-fn search_fla<'a>(name: &'a str, txt: &'a str) -> impl Iterator<Item = usize> + 'a {
-    txt.lines()
-        .filter_map(|l| {
-            let mut parts = l.split_whitespace();
-            let line_name = parts.next()?;
-            if line_name.to_ascii_lowercase() == name.to_ascii_lowercase() {
-                Some(parts.filter_map(|s| s.parse::<usize>().ok()))
-            } else {
-                None
-            }
-        })
-        .flatten()
-}
-
 #[derive(Debug, Default)]
 struct HeaderFla {
     version: String,
@@ -575,3 +542,22 @@ fn update_frame(dest: &mut [u8], data: &[u8], width: usize) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless_engine::HeadlessEngine;
+
+    #[test]
+    fn test_movie_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut game = Game::new(dir.path(), Box::new(HeadlessEngine::new()));
+
+        for movies in [MovieMode::None, MovieMode::FlaPcx] {
+            game.config.movies = movies;
+            assert_eq!(play_anim_fla(&mut game, "dragon3").unwrap(), Wait::Done);
+        }
+        game.config.movies = MovieMode::Fla;
+        assert!(play_anim_fla(&mut game, "dragon3").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::ambiance::Palette;
+use crate::common::{ImageId, RessId, NAME_HQR_RESS};
+use crate::hqr_ress::{decode, HqrArchive, Resource};
+use crate::screen::Image;
+
+/// Default upper bound of the decoded entries kept in the cache.
+pub const DEFAULT_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+type MappedArchive = HqrArchive<Cursor<Mmap>>;
+
+/// Access to all HQR archives of the game data directory.
+///
+/// Archives are memory-mapped lazily on first use and kept open. Decoded entries are kept in a
+/// least recently used cache bounded by the total size of the entries.
+pub struct Resources {
+    root: PathBuf,
+    archives: HashMap<String, MappedArchive>,
+    cache: EntryCache,
+    stats: CacheStats,
+}
+
+impl fmt::Debug for Resources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resources")
+            .field("root", &self.root)
+            .field("archives", &self.archives.keys().collect::<Vec<_>>())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+/// Counters of the resource cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Total size of the entries currently in the cache.
+    pub size: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses, {} bytes cached",
+            self.hits, self.misses, self.size
+        )
+    }
+}
+
+impl Resources {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_cache_size(root, DEFAULT_CACHE_SIZE)
+    }
+
+    pub fn with_cache_size(root: impl Into<PathBuf>, cache_size: usize) -> Self {
+        Self {
+            root: root.into(),
+            archives: Default::default(),
+            cache: EntryCache::new(cache_size),
+            stats: Default::default(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            size: self.cache.size,
+            ..self.stats
+        }
+    }
+
+    /// Returns the archive `name` from the data directory, opening it on first use.
+    pub fn archive(&mut self, name: &str) -> io::Result<&mut MappedArchive> {
+        let key = name.to_ascii_lowercase();
+        if !self.archives.contains_key(&key) {
+            let path = self.root.join(name);
+            let file = File::open(&path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("failed to open {}: {}", path.display(), e),
+                )
+            })?;
+            // SAFETY: the game data is never modified while the game is running.
+            let mmap = unsafe { Mmap::map(&file)? };
+            let archive = HqrArchive::from_reader(Cursor::new(mmap))?;
+            self.archives.insert(key.clone(), archive);
+        }
+        Ok(self
+            .archives
+            .get_mut(&key)
+            .expect("archive was just inserted"))
+    }
+
+    /// Returns the decompressed entry `index` of the archive `name`.
+    pub fn entry(&mut self, name: &str, index: usize) -> io::Result<Arc<[u8]>> {
+        let key = (name.to_ascii_lowercase(), index);
+        if let Some(data) = self.cache.get(&key) {
+            self.stats.hits += 1;
+            return Ok(data);
+        }
+        self.stats.misses += 1;
+
+        let data: Arc<[u8]> = self.archive(name)?.entry(index)?.into();
+        self.cache.insert(key, data.clone());
+        Ok(data)
+    }
+
+    /// Returns the entry `index` of the archive `name` decoded as `R`.
+    pub fn get<R: Resource>(&mut self, name: &str, index: usize) -> io::Result<R> {
+        decode(&self.entry(name, index)?)
+    }
+
+    /// Returns the hidden entry `n` after the entry `index` of the archive `name` decoded as `R`.
+    /// Hidden entries are not cached.
+    pub fn hidden<R: Resource>(&mut self, name: &str, index: usize, n: usize) -> io::Result<R> {
+        decode(&self.archive(name)?.hidden_entry(index, n)?)
+    }
+
+    /// Returns the entry `id` of `ress.hqr` decoded as `R`.
+    pub fn ress<R: Resource>(&mut self, id: RessId) -> io::Result<R> {
+        self.get(NAME_HQR_RESS, id.index())
+    }
+
+    /// Returns the image `id` of `ress.hqr` with its palette.
+    pub fn image(&mut self, id: ImageId) -> io::Result<(Image, Palette)> {
+        Ok((self.ress(id.ress())?, self.ress(id.palette())?))
+    }
+
+    /// Copies the decompressed entry `index` of the archive `name` into `buffer`, returning the
+    /// size of the entry.
+    pub fn load_into(&mut self, name: &str, index: usize, buffer: &mut [u8]) -> io::Result<usize> {
+        let data = self.entry(name, index)?;
+        buffer
+            .get_mut(..data.len())
+            .ok_or_else(|| io::Error::other("buffer too small"))?
+            .copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
+
+impl Drop for Resources {
+    fn drop(&mut self) {
+        log::debug!("resource cache: {}", self.stats());
+    }
+}
+
+type CacheKey = (String, usize);
+
+/// Least recently used cache of entries bounded by the total size of the entries.
+#[derive(Debug)]
+struct EntryCache {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, (Arc<[u8]>, u64)>,
+    /// Keys by the tick of their last use
+    lru: BTreeMap<u64, CacheKey>,
+}
+
+impl EntryCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            tick: 0,
+            entries: Default::default(),
+            lru: Default::default(),
+        }
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        let (data, last_used) = self.entries.get_mut(key)?;
+        self.tick += 1;
+        self.lru.remove(last_used);
+        self.lru.insert(self.tick, key.clone());
+        *last_used = self.tick;
+        Some(data.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, data: Arc<[u8]>) {
+        if data.len() > self.capacity {
+            return;
+        }
+        while self.size + data.len() > self.capacity {
+            let Some((_, evicted)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.entries.remove(&evicted) {
+                self.size -= evicted.len();
+            }
+        }
+
+        self.tick += 1;
+        self.size += data.len();
+        self.lru.insert(self.tick, key.clone());
+        if let Some((replaced, last_used)) = self.entries.insert(key, (data, self.tick)) {
+            self.size -= replaced.len();
+            self.lru.remove(&last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hqr_ress::{CompressMethod, HqrWriter};
+
+    fn key(index: usize) -> CacheKey {
+        ("test.hqr".to_owned(), index)
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = EntryCache::new(10);
+        cache.insert(key(0), vec![0; 4].into());
+        cache.insert(key(1), vec![1; 4].into());
+        assert!(cache.get(&key(0)).is_some());
+
+        // entry 1 is the least recently used
+        cache.insert(key(2), vec![2; 4].into());
+        assert_eq!(cache.size, 8);
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(0)).is_some());
+
+        // both entries are evicted to make room
+        cache.insert(key(3), vec![3; 10].into());
+        assert_eq!(cache.size, 10);
+        assert!(cache.get(&key(0)).is_none());
+        assert!(cache.get(&key(2)).is_none());
+
+        // entries larger than the cache are not kept
+        cache.insert(key(4), vec![4; 11].into());
+        assert!(cache.get(&key(4)).is_none());
+        assert!(cache.get(&key(3)).is_some());
+    }
+
+    #[test]
+    fn test_resources_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = HqrWriter::new();
+        for n in 0..3 {
+            writer.push(vec![n; 4], CompressMethod::Stored);
+        }
+        writer.write(dir.path().join("test.hqr")).unwrap();
+
+        let mut resources = Resources::with_cache_size(dir.path(), 8);
+        assert_eq!(&*resources.entry("test.hqr", 0).unwrap(), [0; 4]);
+        // the archive stays open and mapped once opened
+        std::fs::remove_file(dir.path().join("test.hqr")).unwrap();
+        assert_eq!(&*resources.entry("TEST.HQR", 1).unwrap(), [1; 4]);
+        assert_eq!(&*resources.entry("test.hqr", 0).unwrap(), [0; 4]);
+        assert_eq!(&*resources.entry("test.hqr", 2).unwrap(), [2; 4]);
+        assert_eq!(&*resources.entry("test.hqr", 1).unwrap(), [1; 4]);
+        assert_eq!(resources.archives.len(), 1);
+
+        assert_eq!(
+            resources.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 4,
+                size: 8
+            }
+        );
+    }
+}
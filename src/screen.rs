@@ -1,32 +1,326 @@
+use crate::hqr_ress::{DecodeError, Resource};
+
 pub const WIDTH: usize = 640;
 pub const HEIGHT: usize = 480;
 
-const CLIP_X_MIN: u32 = 0;
-const CLIP_Y_MIN: u32 = 0;
-const CLIP_X_MAX: u32 = 639;
-const CLIP_Y_MAX: u32 = 479;
+/// Rectangle with inclusive bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+}
+
+impl Rect {
+    pub const fn new(x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    /// The whole screen
+    pub const fn screen() -> Self {
+        Self::new(0, 0, WIDTH as i32 - 1, HEIGHT as i32 - 1)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.x1 - self.x0 + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.y1 - self.y0 + 1
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x0..=self.x1).contains(&x) && (self.y0..=self.y1).contains(&y)
+    }
+
+    /// Returns the common part of both rectangles, if any.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect::new(
+            self.x0.max(other.x0),
+            self.y0.max(other.y0),
+            self.x1.min(other.x1),
+            self.y1.min(other.y1),
+        );
+        (rect.x0 <= rect.x1 && rect.y0 <= rect.y1).then_some(rect)
+    }
+
+    pub fn translate(&self, dx: i32, dy: i32) -> Rect {
+        Rect::new(self.x0 + dx, self.y0 + dy, self.x1 + dx, self.y1 + dy)
+    }
+}
 
 #[derive(Debug)]
 pub struct Screen {
-    pub data: [u8; WIDTH * HEIGHT],
+    /// Indexed pixels, kept on the heap since screens are moved around with the game state.
+    pub data: Box<[u8]>,
+    clip: Rect,
 }
 
 impl Default for Screen {
     fn default() -> Self {
         Self {
-            data: [0; WIDTH * HEIGHT],
+            data: vec![0; WIDTH * HEIGHT].into(),
+            clip: Rect::screen(),
+        }
+    }
+}
+
+/// Full-screen indexed image, as stored in the `*_PCR` entries of `ress.hqr`.
+#[derive(Debug)]
+pub struct Image {
+    pub data: Box<[u8]>,
+}
+
+impl Resource for Image {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() != WIDTH * HEIGHT {
+            return Err(DecodeError::InvalidSize {
+                expected: WIDTH * HEIGHT,
+                actual: data.len(),
+            });
         }
+        Ok(Self { data: data.into() })
     }
 }
 
 impl Screen {
+    pub fn load_image(&mut self, image: &Image) {
+        self.data.copy_from_slice(&image.data);
+    }
+
     pub fn copy_to(&self, dst: &mut Screen) {
         dst.data.copy_from_slice(&self.data);
     }
 
-    pub fn draw_line(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, color: u8) {
-        todo!();
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// Restricts drawing to `clip`, intersected with the screen. An empty intersection disables
+    /// drawing until the clip rectangle is changed again.
+    pub fn set_clip(&mut self, clip: Rect) {
+        self.clip = clip
+            .intersect(&Rect::screen())
+            .unwrap_or(Rect::new(0, 0, -1, -1));
     }
 
-    pub fn draw_box(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, color: u8) {}
+    pub fn reset_clip(&mut self) {
+        self.clip = Rect::screen();
+    }
+
+    /// Draws a line from `(x0, y0)` to `(x1, y1)`, both ends included, with Bresenham's
+    /// algorithm. Pixels outside of the clip rectangle are skipped.
+    ///
+    /// Original: `Line`
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        let clip = self.clip;
+        let bounds = Rect::new(x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1));
+        if bounds.intersect(&clip).is_none() {
+            return;
+        }
+
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            if clip.contains(x, y) {
+                self.data[y as usize * WIDTH + x as usize] = color;
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Fills `rect` with `color`.
+    ///
+    /// Original: `Box`
+    pub fn draw_box(&mut self, rect: Rect, color: u8) {
+        for line in self.clipped_lines(rect) {
+            self.data[line].fill(color);
+        }
+    }
+
+    /// Draws the border of `rect` in `color`.
+    pub fn draw_box_outline(&mut self, rect: Rect, color: u8) {
+        let Rect { x0, y0, x1, y1 } = rect;
+        self.draw_line(x0, y0, x1, y0, color);
+        self.draw_line(x0, y1, x1, y1, color);
+        self.draw_line(x0, y0, x0, y1, color);
+        self.draw_line(x1, y0, x1, y1, color);
+    }
+
+    /// Darkens `rect` by `dec` shades, without leaving the 16 color ramp of each pixel.
+    ///
+    /// Original: `ShadeBox`
+    pub fn shade_box(&mut self, rect: Rect, dec: u8) {
+        for line in self.clipped_lines(rect) {
+            for color in &mut self.data[line] {
+                *color = if *color & 0x0F < dec {
+                    *color & 0xF0
+                } else {
+                    *color - dec
+                };
+            }
+        }
+    }
+
+    /// Copies `rect` of this screen to `dst`, with the top left corner at `(xd, yd)`. Only the
+    /// part which is inside the clip rectangle of `dst` is written.
+    ///
+    /// Original: `CopyBlock`
+    pub fn copy_block(&self, rect: Rect, dst: &mut Screen, xd: i32, yd: i32) {
+        let Some(src_rect) = rect.intersect(&Rect::screen()) else {
+            return;
+        };
+        let (dx, dy) = (xd - rect.x0, yd - rect.y0);
+        let Some(dst_rect) = src_rect.translate(dx, dy).intersect(&dst.clip) else {
+            return;
+        };
+        let src_rect = dst_rect.translate(-dx, -dy);
+
+        let len = dst_rect.width() as usize;
+        for (src_y, dst_y) in (src_rect.y0..=src_rect.y1).zip(dst_rect.y0..=dst_rect.y1) {
+            let src = src_y as usize * WIDTH + src_rect.x0 as usize;
+            let dst_idx = dst_y as usize * WIDTH + dst_rect.x0 as usize;
+            dst.data[dst_idx..dst_idx + len].copy_from_slice(&self.data[src..src + len]);
+        }
+    }
+
+    /// Index ranges of the lines of `rect` inside the clip rectangle.
+    fn clipped_lines(&self, rect: Rect) -> impl Iterator<Item = std::ops::Range<usize>> {
+        let rect = rect.intersect(&self.clip);
+        rect.into_iter().flat_map(|rect| {
+            (rect.y0..=rect.y1).map(move |y| {
+                let start = y as usize * WIDTH + rect.x0 as usize;
+                start..start + rect.width() as usize
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Position and color of the pixels which are not 0, line by line.
+    pub(crate) fn colored_pixels(screen: &Screen) -> Vec<(usize, usize, u8)> {
+        screen
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, &color)| color != 0)
+            .map(|(idx, &color)| (idx % WIDTH, idx / WIDTH, color))
+            .collect()
+    }
+
+    fn pixels(screen: &Screen) -> Vec<(usize, usize)> {
+        colored_pixels(screen)
+            .into_iter()
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_line_horizontal() {
+        let mut screen = Screen::default();
+        screen.draw_line(10, 5, 14, 5, 1);
+        assert_eq!(
+            pixels(&screen),
+            [(10, 5), (11, 5), (12, 5), (13, 5), (14, 5)]
+        );
+
+        let mut reversed = Screen::default();
+        reversed.draw_line(14, 5, 10, 5, 1);
+        assert_eq!(pixels(&screen), pixels(&reversed));
+    }
+
+    #[test]
+    fn test_draw_line_vertical() {
+        let mut screen = Screen::default();
+        screen.draw_line(639, 476, 639, 479, 7);
+        assert_eq!(
+            pixels(&screen),
+            [(639, 476), (639, 477), (639, 478), (639, 479)]
+        );
+        assert_eq!(screen.data[479 * WIDTH + 639], 7);
+    }
+
+    #[test]
+    fn test_draw_line_diagonal() {
+        let mut screen = Screen::default();
+        screen.draw_line(3, 0, 0, 3, 1);
+        assert_eq!(pixels(&screen), [(3, 0), (2, 1), (1, 2), (0, 3)]);
+
+        let mut screen = Screen::default();
+        screen.draw_line(0, 0, 4, 2, 1);
+        assert_eq!(pixels(&screen), [(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+    }
+
+    #[test]
+    fn test_draw_line_clipped() {
+        let mut screen = Screen::default();
+        screen.draw_line(-5, 10, 2, 10, 1);
+        assert_eq!(pixels(&screen), [(0, 10), (1, 10), (2, 10)]);
+
+        let mut screen = Screen::default();
+        screen.draw_line(-10, -10, -1, 500, 1);
+        screen.draw_line(640, 0, 700, 479, 1);
+        screen.draw_line(0, 480, 639, 480, 1);
+        assert!(pixels(&screen).is_empty());
+
+        let mut screen = Screen::default();
+        screen.set_clip(Rect::new(100, 100, 200, 200));
+        screen.draw_line(0, 0, 99, 300, 1);
+        screen.draw_line(0, 150, 1000, 150, 1);
+        assert_eq!(pixels(&screen).len(), 101);
+        assert!(pixels(&screen)
+            .iter()
+            .all(|&(x, y)| y == 150 && (100..=200).contains(&x)));
+    }
+
+    #[test]
+    fn test_draw_box_clipped() {
+        let mut screen = Screen::default();
+        screen.draw_box(Rect::new(-2, 478, 1, 500), 3);
+        assert_eq!(pixels(&screen), [(0, 478), (1, 478), (0, 479), (1, 479)]);
+    }
+
+    #[test]
+    fn test_shade_box() {
+        let mut screen = Screen::default();
+        screen.data[..4].copy_from_slice(&[0x12, 0x1F, 0x20, 0x35]);
+        screen.shade_box(Rect::new(0, 0, 2, 0), 4);
+        assert_eq!(screen.data[..4], [0x10, 0x1B, 0x20, 0x35]);
+    }
+
+    #[test]
+    fn test_copy_block() {
+        let mut src = Screen::default();
+        src.draw_box(Rect::new(10, 10, 12, 11), 5);
+        let mut dst = Screen::default();
+        src.copy_block(Rect::new(10, 10, 12, 11), &mut dst, 638, 0);
+        assert_eq!(pixels(&dst), [(638, 0), (639, 0), (638, 1), (639, 1)]);
+
+        let mut dst = Screen::default();
+        dst.set_clip(Rect::new(0, 0, 100, 100));
+        src.copy_block(Rect::new(10, 10, 12, 11), &mut dst, 100, -1);
+        assert_eq!(pixels(&dst), [(100, 0)]);
+    }
 }
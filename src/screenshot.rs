@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ambiance::Palette;
+use crate::screen::{HEIGHT, WIDTH};
+
+/// Color type of an exported PNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngFormat {
+    /// 24 bit RGB, with the palette applied.
+    #[default]
+    Truecolor,
+    /// 8 bit indexed, with the palette stored in the file.
+    Paletted,
+}
+
+/// Encodes an indexed 640x480 screen shown with `palette` as PNG.
+pub fn write_png(
+    writer: impl Write,
+    pixels: &[u8],
+    palette: &Palette,
+    format: PngFormat,
+) -> io::Result<()> {
+    if pixels.len() != WIDTH * HEIGHT {
+        return Err(io::Error::other("unexpected screen size"));
+    }
+
+    let mut encoder = png::Encoder::new(writer, WIDTH as u32, HEIGHT as u32);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data = match format {
+        PngFormat::Truecolor => {
+            encoder.set_color(png::ColorType::Rgb);
+            pixels
+                .iter()
+                .flat_map(|&color| {
+                    let idx = usize::from(color) * 3;
+                    palette.data[idx..idx + 3].iter().copied()
+                })
+                .collect()
+        }
+        PngFormat::Paletted => {
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_palette(&palette.data[..]);
+            pixels.to_vec()
+        }
+    };
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&data).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+pub fn save_png(
+    path: impl AsRef<Path>,
+    pixels: &[u8],
+    palette: &Palette,
+    format: PngFormat,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path.as_ref())?);
+    write_png(&mut writer, pixels, palette, format)?;
+    writer.flush()
+}
+
+/// Returns the first path `lba1-NNNN.png` in `dir` which does not exist yet.
+pub fn next_screenshot_path(dir: impl AsRef<Path>) -> PathBuf {
+    (0..)
+        .map(|n| dir.as_ref().join(format!("lba1-{:04}.png", n)))
+        .find(|path| !path.exists())
+        .expect("infinite iterator")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamemenu::{flip, Game};
+    use crate::headless_engine::HeadlessEngine;
+    use crate::screen::Rect;
+
+    fn decode_png(path: &Path) -> (png::OutputInfo, Vec<u8>, Option<Vec<u8>>) {
+        let data = std::fs::read(path).unwrap();
+        let mut decoder = png::Decoder::new(io::Cursor::new(data));
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        let palette = reader
+            .info()
+            .palette
+            .as_ref()
+            .map(|palette| palette.to_vec());
+        (info, buffer, palette)
+    }
+
+    #[test]
+    fn test_headless_screenshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut game = Game::new(dir.path(), Box::new(HeadlessEngine::new()));
+        let mut palette = Palette::default();
+        palette.data[3..6].copy_from_slice(&[10, 20, 30]);
+        palette.data[6..9].copy_from_slice(&[255, 128, 0]);
+        game.engine.palette(&palette);
+        game.log.draw_box(Rect::new(0, 0, 1, 0), 1);
+        game.log.draw_box(Rect::new(639, 479, 639, 479), 2);
+        flip(&mut game);
+
+        let path = dir.path().join("paletted.png");
+        game.screenshot(&path, PngFormat::Paletted).unwrap();
+        let (info, pixels, png_palette) = decode_png(&path);
+        assert_eq!((info.width, info.height), (WIDTH as u32, HEIGHT as u32));
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        assert_eq!(pixels, game.log.data.to_vec());
+        assert_eq!(png_palette.as_deref(), Some(&palette.data[..]));
+
+        let path = dir.path().join("truecolor.png");
+        game.screenshot(&path, PngFormat::Truecolor).unwrap();
+        let (info, pixels, _) = decode_png(&path);
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(pixels[..9], [10, 20, 30, 10, 20, 30, 0, 0, 0]);
+        assert_eq!(pixels[pixels.len() - 3..], [255, 128, 0]);
+
+        assert!(write_png(io::sink(), &[0; 10], &palette, PngFormat::Truecolor).is_err());
+    }
+}
@@ -1,22 +1,61 @@
 use std::time::Duration;
 
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::controller::{Button, GameController};
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Keycode;
 use sdl2::pixels::{Color, Palette as SdlPalette, PixelFormatEnum};
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::surface::Surface;
 use sdl2::video::Window;
-use sdl2::EventPump;
+use sdl2::{AudioSubsystem, EventPump, GameControllerSubsystem};
 
 use crate::ambiance::Palette;
+use crate::backend::{Backend, Event, Key};
+use crate::screenshot::{next_screenshot_path, save_png, PngFormat};
+use crate::voc::Sample;
 
 pub const SCREEN_WIDTH: u32 = 640;
 pub const SCREEN_HEIGHT: u32 = 480;
 
+/// Saves the shown frame as PNG into the working directory.
+const SCREENSHOT_KEY: Keycode = Keycode::F12;
+
+/// Settings of the game window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowOptions {
+    /// Size of a pixel of the game in the window.
+    pub scale: u32,
+    /// Covers the whole desktop, the frame is scaled as much as it fits.
+    pub fullscreen: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            fullscreen: false,
+        }
+    }
+}
+
 pub struct SdlEngine {
     pub window_canvas: Canvas<Window>,
     pub event_pump: EventPump,
     buffer_surface: Surface<'static>,
+    /// Frame in the pixel format of the window, kept up to date for scaling.
+    converted_surface: Option<Surface<'static>>,
     colors_buffer: Vec<Color>,
+    palette: Palette,
+    /// `None` when no audio device is available
+    audio: Option<AudioSubsystem>,
+    /// Queue of the sample being played, opened at the rate of the sample.
+    sample_queue: Option<AudioQueue<u8>>,
+    /// `None` when game controllers are not supported
+    controller_subsystem: Option<GameControllerSubsystem>,
+    /// Connected game controllers, which are closed when dropped.
+    controllers: Vec<GameController>,
 }
 
 impl std::fmt::Debug for SdlEngine {
@@ -26,14 +65,21 @@ impl std::fmt::Debug for SdlEngine {
 }
 
 impl SdlEngine {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(options: WindowOptions) -> anyhow::Result<Self> {
         let sdl_context = sdl2::init().map_err(anyhow::Error::msg)?;
         let video_subsystem = sdl_context.video().map_err(anyhow::Error::msg)?;
 
-        let window = video_subsystem
-            .window("Little Big Adventure", SCREEN_WIDTH, SCREEN_HEIGHT)
-            .position_centered()
-            .build()?;
+        let scale = options.scale.max(1);
+        let mut window = video_subsystem.window(
+            "Little Big Adventure",
+            SCREEN_WIDTH * scale,
+            SCREEN_HEIGHT * scale,
+        );
+        window.position_centered();
+        if options.fullscreen {
+            window.fullscreen_desktop();
+        }
+        let window = window.build()?;
 
         let mut window_canvas = window.into_canvas().present_vsync().build()?;
 
@@ -43,54 +89,114 @@ impl SdlEngine {
 
         let event_pump = sdl_context.event_pump().map_err(anyhow::Error::msg)?;
 
+        let audio = sdl_context
+            .audio()
+            .map_err(|e| log::warn!("failed to init sdl audio, sound is disabled: {}", e))
+            .ok();
+        // connected controllers are reported with a device added event
+        let controller_subsystem = sdl_context
+            .game_controller()
+            .map_err(|e| log::warn!("failed to init sdl game controllers: {}", e))
+            .ok();
+
         Ok(Self {
             window_canvas,
             event_pump,
             buffer_surface: Surface::new(SCREEN_WIDTH, SCREEN_HEIGHT, PixelFormatEnum::Index8)
                 .map_err(anyhow::Error::msg)?,
+            converted_surface: None,
             colors_buffer: vec![Color::BLACK; 256],
+            palette: Palette::default(),
+            audio,
+            sample_queue: None,
+            controller_subsystem,
+            controllers: Vec::new(),
         })
     }
 
-    pub fn palette(&mut self, pal: &Palette) {
-        self.colors_buffer.resize(pal.data.len() / 3, Color::BLACK);
-        for (i, rgb) in pal.data.chunks_exact(3).enumerate() {
-            self.colors_buffer[i] = Color::RGB(rgb[0], rgb[1], rgb[2]);
+    fn open_controller(&mut self, joystick_index: u32) {
+        let Some(subsystem) = &self.controller_subsystem else {
+            return;
+        };
+        match subsystem.open(joystick_index) {
+            Ok(controller) => self.controllers.push(controller),
+            Err(e) => log::warn!("failed to open game controller {}: {}", joystick_index, e),
         }
+    }
 
-        let sdl_pal = SdlPalette::with_colors(self.colors_buffer.as_slice()).unwrap();
-        self.buffer_surface.set_palette(&sdl_pal).unwrap();
-
+    /// Shows `rect` of the frame, or the whole frame, in the window.
+    fn present(&mut self, rect: Option<Rect>) {
         let mut screen_surface = self
             .window_canvas
             .window()
             .surface(&self.event_pump)
             .unwrap();
-        self.buffer_surface
-            .blit(None, &mut screen_surface, None)
-            .unwrap();
+
+        let src = rect.unwrap_or_else(|| Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT));
+        let dst = scaled_rect(src, screen_surface.width(), screen_surface.height());
+        if dst == src {
+            self.buffer_surface
+                .blit(src, &mut screen_surface, dst)
+                .unwrap();
+            // only updated while scaling
+            self.converted_surface = None;
+        } else {
+            // SDL can not scale indexed surfaces, the changed part of the frame is converted first
+            let format = screen_surface.pixel_format_enum();
+            let mut converted_rect = src;
+            if self
+                .converted_surface
+                .as_ref()
+                .is_none_or(|surface| surface.pixel_format_enum() != format)
+            {
+                self.converted_surface =
+                    Some(Surface::new(SCREEN_WIDTH, SCREEN_HEIGHT, format).unwrap());
+                converted_rect = Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+            }
+            let frame = self.converted_surface.as_mut().unwrap();
+            self.buffer_surface
+                .blit(converted_rect, frame, converted_rect)
+                .unwrap();
+            frame.blit_scaled(src, &mut screen_surface, dst).unwrap();
+        }
         screen_surface.finish().unwrap();
     }
 
-    pub fn copy_from_buffer(&mut self, buf: &[u8]) {
+    fn save_screenshot(&self) {
+        let (pixels, palette) = self.capture();
+        let path = next_screenshot_path(".");
+        match save_png(&path, &pixels, &palette, PngFormat::Truecolor) {
+            Ok(()) => log::info!("saved screenshot to {}", path.display()),
+            Err(e) => log::error!("failed to save screenshot to {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Backend for SdlEngine {
+    fn palette(&mut self, pal: &Palette) {
+        self.palette.data.copy_from_slice(&pal.data);
+        self.colors_buffer.resize(pal.data.len() / 3, Color::BLACK);
+        for (i, rgb) in pal.data.chunks_exact(3).enumerate() {
+            self.colors_buffer[i] = Color::RGB(rgb[0], rgb[1], rgb[2]);
+        }
+
+        let sdl_pal = SdlPalette::with_colors(self.colors_buffer.as_slice()).unwrap();
+        self.buffer_surface.set_palette(&sdl_pal).unwrap();
+
+        self.present(None);
+    }
+
+    fn copy_from_buffer(&mut self, buf: &[u8]) {
         self.buffer_surface.with_lock_mut(|data| {
             data.copy_from_slice(buf);
         });
     }
 
-    pub fn flip(&mut self) {
-        let mut screen_surface = self
-            .window_canvas
-            .window()
-            .surface(&self.event_pump)
-            .unwrap();
-        self.buffer_surface
-            .blit(None, &mut screen_surface, None)
-            .unwrap();
-        screen_surface.finish().unwrap();
+    fn flip(&mut self) {
+        self.present(None);
     }
 
-    pub fn copy_block_phys(&mut self, buf: &[u8], x0: u32, y0: u32, x1: u32, y1: u32) {
+    fn copy_block_phys(&mut self, buf: &[u8], x0: u32, y0: u32, x1: u32, y1: u32) {
         self.buffer_surface.with_lock_mut(|data| {
             let offset = x0 as usize;
             let len = x1.saturating_sub(x0) as usize;
@@ -100,26 +206,137 @@ impl SdlEngine {
                     .copy_from_slice(&buf[line + offset..line + offset + len]);
             }
         });
-        let mut screen_surface = self
-            .window_canvas
-            .window()
-            .surface(&self.event_pump)
-            .unwrap();
-
         let rect = Rect::new(
             x0 as i32,
             y0 as i32,
             x1.saturating_sub(x0),
             y1.saturating_sub(y0),
         );
-        self.buffer_surface
-            .blit(Some(rect), &mut screen_surface, Some(rect))
-            .unwrap();
-        screen_surface.finish().unwrap();
+        self.present(Some(rect));
     }
 
-    pub fn set_black_pal(&mut self) {
-        self.palette(&Palette::default());
+    fn capture(&self) -> (Vec<u8>, Palette) {
+        let pixels = self.buffer_surface.with_lock(|data| data.to_vec());
+        (pixels, self.palette.clone())
+    }
+
+    fn poll_event(&mut self) -> Option<Event> {
+        loop {
+            let event = match self.event_pump.poll_event()? {
+                SdlEvent::Quit { .. } => Event::Quit,
+                SdlEvent::KeyDown {
+                    keycode: Some(SCREENSHOT_KEY),
+                    repeat: false,
+                    ..
+                } => {
+                    self.save_screenshot();
+                    continue;
+                }
+                // held arrow keys repeat, to move through menus and change volumes
+                SdlEvent::KeyDown {
+                    keycode: Some(keycode),
+                    repeat,
+                    ..
+                } if !repeat || key(keycode).is_arrow() => Event::KeyDown(key(keycode)),
+                SdlEvent::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => Event::KeyUp(key(keycode)),
+                SdlEvent::ControllerDeviceAdded { which, .. } => {
+                    self.open_controller(which);
+                    continue;
+                }
+                SdlEvent::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|controller| controller.instance_id() != which);
+                    continue;
+                }
+                SdlEvent::ControllerButtonDown { button, .. } => Event::KeyDown(button_key(button)),
+                SdlEvent::ControllerButtonUp { button, .. } => Event::KeyUp(button_key(button)),
+                _ => continue,
+            };
+            return Some(event);
+        }
+    }
+
+    fn play_sample(&mut self, sample: &Sample, volume: u32) {
+        self.stop_sample();
+        let Some(audio) = &self.audio else {
+            return;
+        };
+        let spec = AudioSpecDesired {
+            freq: Some(sample.rate as i32),
+            channels: Some(1),
+            samples: None,
+        };
+        match audio.open_queue::<u8, _>(None, &spec) {
+            Ok(queue) => {
+                if !queue.queue(&scale_volume(&sample.data, volume)) {
+                    log::warn!("failed to queue sample: {}", sdl2::get_error());
+                    return;
+                }
+                queue.resume();
+                self.sample_queue = Some(queue);
+            }
+            Err(e) => log::warn!("failed to open audio device: {}", e),
+        }
+    }
+
+    fn stop_sample(&mut self) {
+        self.sample_queue = None;
+    }
+
+    fn is_sample_playing(&self) -> bool {
+        self.sample_queue
+            .as_ref()
+            .is_some_and(|queue| queue.size() > 0)
+    }
+}
+
+/// Position of `rect` of the frame in a window of `width` x `height`, scaled by the largest
+/// integer factor which fits and centered.
+fn scaled_rect(rect: Rect, width: u32, height: u32) -> Rect {
+    let scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT).max(1);
+    let x = width.saturating_sub(SCREEN_WIDTH * scale) / 2;
+    let y = height.saturating_sub(SCREEN_HEIGHT * scale) / 2;
+    Rect::new(
+        x as i32 + rect.x() * scale as i32,
+        y as i32 + rect.y() * scale as i32,
+        rect.width() * scale,
+        rect.height() * scale,
+    )
+}
+
+/// Scales unsigned 8 bit PCM data around its center by `volume` (0 to 255).
+fn scale_volume(data: &[u8], volume: u32) -> Vec<u8> {
+    let volume = volume.min(255) as i32;
+    data.iter()
+        .map(|&v| (128 + (i32::from(v) - 128) * volume / 255) as u8)
+        .collect()
+}
+
+fn key(keycode: Keycode) -> Key {
+    match keycode {
+        Keycode::Escape => Key::Escape,
+        Keycode::Return | Keycode::KpEnter => Key::Enter,
+        Keycode::Space => Key::Space,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        _ => Key::Other,
+    }
+}
+
+fn button_key(button: Button) -> Key {
+    match button {
+        Button::A | Button::Start => Key::Enter,
+        Button::B | Button::Back => Key::Escape,
+        Button::DPadUp => Key::Up,
+        Button::DPadDown => Key::Down,
+        Button::DPadLeft => Key::Left,
+        Button::DPadRight => Key::Right,
+        _ => Key::Other,
     }
 }
 
@@ -0,0 +1,138 @@
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+
+use crate::hqr_ress::{read_offset_table, DecodeError, Resource};
+
+/// Sprite in the run-length encoded brick format.
+///
+/// Every line of a brick is a list of runs; a run either skips transparent pixels, copies
+/// literal colors or repeats a single color.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub width: u8,
+    pub height: u8,
+    pub offset_x: u8,
+    pub offset_y: u8,
+    /// Decoded pixels, line by line; transparent pixels are `None`.
+    pixels: Vec<Option<u8>>,
+}
+
+impl Sprite {
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width.into() {
+            return None;
+        }
+        self.pixels
+            .get(y * usize::from(self.width) + x)
+            .copied()
+            .flatten()
+    }
+
+    fn from_reader(mut reader: impl Read) -> Result<Self, DecodeError> {
+        let width = reader.read_u8()?;
+        let height = reader.read_u8()?;
+        let offset_x = reader.read_u8()?;
+        let offset_y = reader.read_u8()?;
+
+        let mut pixels = vec![None; usize::from(width) * usize::from(height)];
+        if width > 0 {
+            for line in pixels.chunks_exact_mut(width.into()) {
+                let num_runs = reader.read_u8()?;
+                let mut x = 0;
+                for _ in 0..num_runs {
+                    let run = reader.read_u8()?;
+                    let len = usize::from(run & 0x3F) + 1;
+                    let dst = line
+                        .get_mut(x..x + len)
+                        .ok_or(DecodeError::Invalid("brick run length"))?;
+                    match run >> 6 {
+                        0 => {}
+                        2 => dst.fill(Some(reader.read_u8()?)),
+                        _ => {
+                            for pixel in dst {
+                                *pixel = Some(reader.read_u8()?);
+                            }
+                        }
+                    }
+                    x += len;
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            offset_x,
+            offset_y,
+            pixels,
+        })
+    }
+}
+
+impl Resource for Sprite {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        Self::from_reader(data)
+    }
+}
+
+/// Bank of bricks behind a table of offsets, like the `*_GPH` entries of `ress.hqr`.
+#[derive(Debug, Clone)]
+pub struct SpriteBank {
+    pub sprites: Vec<Sprite>,
+}
+
+impl Resource for SpriteBank {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let sprites = read_offset_table(data)?
+            .into_iter()
+            .map(|offset| Sprite::from_reader(&data[offset..]))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { sprites })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_sprite() {
+        let data = [
+            3, 2, 1, 4, // size and offset
+            2, 0x00, 0x81, 7, // one transparent pixel, then color 7 twice
+            1, 0x42, 1, 2, 3, // three literal colors
+        ];
+        let sprite = Sprite::decode(&data).unwrap();
+        assert_eq!((sprite.offset_x, sprite.offset_y), (1, 4));
+        let pixels: Vec<_> = (0..2)
+            .flat_map(|y| (0..3).map(move |x| (x, y)))
+            .map(|(x, y)| sprite.pixel(x, y))
+            .collect();
+        assert_eq!(pixels, [None, Some(7), Some(7), Some(1), Some(2), Some(3)]);
+        assert_eq!(sprite.pixel(3, 0), None);
+
+        assert!(matches!(
+            Sprite::decode(&data[..11]),
+            Err(DecodeError::Truncated)
+        ));
+        // a run of 2 pixels in a line of 1 pixel
+        assert!(matches!(
+            Sprite::decode(&[1, 1, 0, 0, 1, 0x01]),
+            Err(DecodeError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_sprite_bank() {
+        let data = [8, 0, 0, 0, 15, 0, 0, 0, 1, 1, 0, 0, 1, 0x80, 5, 0, 0, 0, 0];
+        let bank = SpriteBank::decode(&data).unwrap();
+        assert_eq!(bank.sprites.len(), 2);
+        assert_eq!(bank.sprites[0].pixel(0, 0), Some(5));
+        assert_eq!(bank.sprites[1].width, 0);
+    }
+}
@@ -0,0 +1,481 @@
+//! Export of the texts of `text.hqr` to JSON and gettext PO files for translators, and import of
+//! the edited files back into a `text.hqr`.
+//!
+//! The JSON file holds every language with its banks, keyed by the language code, the bank name
+//! and the text id:
+//!
+//! ```json
+//! { "EN": { "sys": { "20": "New Game" } } }
+//! ```
+//!
+//! A PO file holds one language, with the English text as `msgid` and `bank/id` as `msgctxt`.
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Seek, Write};
+
+use indexmap::IndexMap;
+
+use crate::codepage;
+use crate::hqr_ress::{decode, HqrArchive, HqrWriter};
+use crate::message::{
+    bank_index, bank_name, bank_texts, Language, TextBank, TextOrder, MAX_TEXT_LANG,
+};
+
+/// Texts of one bank in one language, in the order of the bank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankTexts {
+    pub language: Language,
+    /// Index of the bank, see [`bank_name`].
+    pub bank: usize,
+    pub texts: Vec<(u16, String)>,
+}
+
+impl BankTexts {
+    pub fn get(&self, id: u16) -> Option<&str> {
+        self.texts
+            .iter()
+            .find(|(text_id, _)| *text_id == id)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+/// Reads every bank of every language of `text.hqr`. Banks which are missing or empty in the
+/// archive are skipped.
+pub fn read_banks<R: Read + Seek>(archive: &mut HqrArchive<R>) -> io::Result<Vec<BankTexts>> {
+    let mut banks = Vec::new();
+    for language in Language::ALL {
+        for bank in 0..MAX_TEXT_LANG {
+            let index = bank_index(language, bank);
+            let present = |index| {
+                archive
+                    .info(index)
+                    .is_some_and(|info| !info.is_blank() && info.size > 0)
+            };
+            if !present(index) || !present(index + 1) {
+                continue;
+            }
+            let order: TextOrder = archive.get(index)?;
+            let texts: TextBank = archive.get(index + 1)?;
+            banks.push(BankTexts {
+                language,
+                bank,
+                texts: bank_texts(&order, &texts),
+            });
+        }
+    }
+    Ok(banks)
+}
+
+/// Rebuilds `text.hqr` from `archive` with the texts of `banks`.
+///
+/// Texts of an edited bank replace the text with the same id, new ids are appended to the bank.
+/// All other entries are copied unchanged, blank entries stay blank. Fails if an edited bank is
+/// not part of the archive.
+pub fn rebuild<R: Read + Seek>(
+    archive: &mut HqrArchive<R>,
+    banks: &[BankTexts],
+) -> io::Result<HqrWriter> {
+    if let Some(bank) = banks
+        .iter()
+        .find(|bank| bank_index(bank.language, bank.bank) + 1 >= archive.len())
+    {
+        return Err(invalid(format!(
+            "text bank {} of {} is missing from the archive",
+            bank_name(bank.bank).unwrap_or_default(),
+            bank.language
+        )));
+    }
+
+    let mut writer = HqrWriter::new();
+    let mut index = 0;
+    while index < archive.len() {
+        let info = *archive.info(index).expect("index is in bounds");
+        let edited = banks
+            .iter()
+            .filter(|bank| bank_index(bank.language, bank.bank) == index)
+            .collect::<Vec<_>>();
+        if edited.is_empty() {
+            if info.is_blank() {
+                writer.push_blank();
+            } else {
+                writer.push(archive.entry(index)?, info.compress_method);
+            }
+            index += 1;
+            continue;
+        }
+
+        let text_info = *archive.info(index + 1).expect("index is in bounds");
+        let mut texts = if info.is_blank() || text_info.is_blank() {
+            Vec::new()
+        } else {
+            bank_texts(
+                &decode(&archive.entry(index)?)?,
+                &decode(&archive.entry(index + 1)?)?,
+            )
+        };
+        for (id, text) in edited.iter().flat_map(|bank| &bank.texts) {
+            match texts.iter_mut().find(|(text_id, _)| text_id == id) {
+                Some((_, old)) => old.clone_from(text),
+                None => texts.push((*id, text.clone())),
+            }
+        }
+
+        let order = TextOrder {
+            ids: texts.iter().map(|(id, _)| *id).collect(),
+        };
+        let encoded = texts
+            .iter()
+            .map(|(id, text)| encode_text(text).map_err(|e| invalid(format!("text {}: {}", id, e))))
+            .collect::<io::Result<Vec<_>>>()?;
+        let bank = TextBank::from_texts(&encoded)
+            .ok_or_else(|| invalid(format!("text bank {} too large", index + 1)))?;
+
+        writer.push(order.to_bytes(), info.compress_method);
+        writer.push(bank.as_bytes(), text_info.compress_method);
+        index += 2;
+    }
+    Ok(writer)
+}
+
+fn encode_text(text: &str) -> Result<Vec<u8>, String> {
+    text.chars()
+        .map(|c| {
+            codepage::encode_char(c)
+                .filter(|&b| b != 0)
+                .ok_or_else(|| format!("character {:?} is not part of the game codepage", c))
+        })
+        .collect()
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn bank_by_name(name: &str) -> io::Result<usize> {
+    (0..MAX_TEXT_LANG)
+        .find(|&bank| bank_name(bank) == Some(name))
+        .ok_or_else(|| invalid(format!("unknown text bank {:?}", name)))
+}
+
+/// Texts of the JSON file, keyed by language code, bank name and text id in the order of the
+/// game.
+type JsonTexts<S> = IndexMap<S, IndexMap<S, IndexMap<u16, S>>>;
+
+/// Writes `banks` as a JSON object keyed by language, bank name and text id.
+pub fn write_json(mut writer: impl Write, banks: &[BankTexts]) -> io::Result<()> {
+    let mut json: JsonTexts<&str> = IndexMap::new();
+    for bank in banks {
+        let name = bank_name(bank.bank).unwrap_or_default();
+        let texts = bank
+            .texts
+            .iter()
+            .map(|(id, text)| (*id, text.as_str()))
+            .collect();
+        json.entry(bank.language.code())
+            .or_default()
+            .insert(name, texts);
+    }
+    serde_json::to_writer_pretty(&mut writer, &json)?;
+    writeln!(writer)
+}
+
+/// Parses a JSON file written by [`write_json`].
+pub fn parse_json(json: &str) -> io::Result<Vec<BankTexts>> {
+    let json: JsonTexts<String> = serde_json::from_str(json)?;
+    let mut banks = Vec::new();
+    for (code, language_banks) in json {
+        let language: Language = code.parse().map_err(invalid)?;
+        for (name, texts) in language_banks {
+            banks.push(BankTexts {
+                language,
+                bank: bank_by_name(&name)?,
+                texts: texts.into_iter().collect(),
+            });
+        }
+    }
+    Ok(banks)
+}
+
+/// Writes the texts of `language` as PO file, with the English texts as source.
+pub fn write_po(mut writer: impl Write, banks: &[BankTexts], language: Language) -> io::Result<()> {
+    let mut po = String::new();
+    po.push_str("msgid \"\"\nmsgstr \"\"\n");
+    po.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    let _ = writeln!(po, "\"Language: {}\\n\"", language.iso_code());
+
+    for bank in banks.iter().filter(|bank| bank.language == language) {
+        let source = banks
+            .iter()
+            .find(|source| source.language == Language::English && source.bank == bank.bank);
+        let name = bank_name(bank.bank).unwrap_or_default();
+        for (id, text) in &bank.texts {
+            let msgid = source.and_then(|source| source.get(*id)).unwrap_or(text);
+            let _ = write!(
+                po,
+                "\nmsgctxt {}\nmsgid {}\nmsgstr {}\n",
+                po_string(&format!("{}/{}", name, id)),
+                po_string(msgid),
+                po_string(text)
+            );
+        }
+    }
+    writer.write_all(po.as_bytes())
+}
+
+fn po_string(s: &str) -> String {
+    let mut po = String::with_capacity(s.len() + 2);
+    po.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => po.push_str("\\\""),
+            '\\' => po.push_str("\\\\"),
+            '\n' => po.push_str("\\n"),
+            '\t' => po.push_str("\\t"),
+            c if c.is_ascii_control() => {
+                let _ = write!(po, "\\{:03o}", c as u32);
+            }
+            c => po.push(c),
+        }
+    }
+    po.push('"');
+    po
+}
+
+fn parse_po_string(line: &str, line_number: usize) -> io::Result<String> {
+    let error = |what: &str| invalid(format!("line {}: {}", line_number, what));
+    let inner = line
+        .strip_prefix('"')
+        .and_then(|line| line.strip_suffix('"'))
+        .ok_or_else(|| error("expected quoted string"))?;
+
+    let mut s = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => s.push('\n'),
+            Some('t') => s.push('\t'),
+            Some(c @ '0'..='7') => {
+                // up to three octal digits, only ASCII is allowed in a UTF-8 file
+                let mut code = c.to_digit(8).expect("octal digit");
+                for _ in 0..2 {
+                    match chars.clone().next().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                let c = char::from_u32(code)
+                    .filter(char::is_ascii)
+                    .ok_or_else(|| error("invalid octal escape"))?;
+                s.push(c);
+            }
+            Some(c @ ('"' | '\\')) => s.push(c),
+            _ => return Err(error("invalid escape")),
+        }
+    }
+    Ok(s)
+}
+
+/// Parses a PO file written by [`write_po`]. Untranslated entries, with an empty `msgstr`, are
+/// left out so that [`rebuild`] keeps the texts of the archive for them.
+pub fn parse_po(po: &str) -> io::Result<Vec<BankTexts>> {
+    // (keyword, value) of every statement, with continuation lines joined
+    let mut statements: Vec<(String, String)> = Vec::new();
+    for (n, line) in po.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('"') {
+            let (_, value) = statements
+                .last_mut()
+                .ok_or_else(|| invalid(format!("line {}: string without keyword", n + 1)))?;
+            value.push_str(&parse_po_string(line, n + 1)?);
+            continue;
+        }
+        let (keyword, value) = line
+            .split_once(' ')
+            .ok_or_else(|| invalid(format!("line {}: expected keyword and string", n + 1)))?;
+        statements.push((keyword.to_owned(), parse_po_string(value.trim(), n + 1)?));
+    }
+
+    let mut language = None;
+    let mut banks: Vec<BankTexts> = Vec::new();
+    let mut context = None;
+    for (keyword, value) in statements {
+        match keyword.as_str() {
+            "msgctxt" => context = Some(value),
+            // the English source text, the translation is the msgstr
+            "msgid" => {}
+            "msgstr" => {
+                let Some(context) = context.take() else {
+                    // header entry
+                    language = value
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Language:"))
+                        .map(|code| code.trim().parse::<Language>())
+                        .transpose()
+                        .map_err(invalid)?;
+                    continue;
+                };
+                let language = language.ok_or_else(|| invalid("po file without language"))?;
+                let (name, id) = context
+                    .split_once('/')
+                    .ok_or_else(|| invalid(format!("invalid msgctxt {:?}", context)))?;
+                let bank = bank_by_name(name)?;
+                let id = id
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid text id in msgctxt {:?}", context)))?;
+                if value.is_empty() {
+                    continue;
+                }
+
+                let index = match banks.iter().position(|texts| texts.bank == bank) {
+                    Some(index) => index,
+                    None => {
+                        banks.push(BankTexts {
+                            language,
+                            bank,
+                            texts: Vec::new(),
+                        });
+                        banks.len() - 1
+                    }
+                };
+                banks[index].texts.push((id, value));
+            }
+            keyword => return Err(invalid(format!("unsupported po keyword {:?}", keyword))),
+        }
+    }
+    Ok(banks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::hqr_ress::CompressMethod;
+
+    fn banks() -> Vec<BankTexts> {
+        vec![
+            BankTexts {
+                language: Language::English,
+                bank: 0,
+                texts: vec![(20, "New Game".into()), (21, "Say \"hi\"@\\".into())],
+            },
+            BankTexts {
+                language: Language::French,
+                bank: 0,
+                texts: vec![
+                    (20, "Nouvelle partie".into()),
+                    (21, "Paramètres\x01".into()),
+                ],
+            },
+        ]
+    }
+
+    /// Archive with the first bank of English and French, the other banks are blank.
+    fn archive() -> HqrArchive<Cursor<Vec<u8>>> {
+        let mut writer = HqrWriter::new();
+        for language in [Language::English, Language::French] {
+            let bank = &banks()[language.index()];
+            let order = TextOrder {
+                ids: bank.texts.iter().map(|(id, _)| *id).collect(),
+            };
+            let texts: Vec<_> = bank
+                .texts
+                .iter()
+                .map(|(_, text)| encode_text(text).unwrap())
+                .collect();
+            writer.push(order.to_bytes(), CompressMethod::Stored);
+            writer.push(
+                TextBank::from_texts(&texts).unwrap().as_bytes(),
+                CompressMethod::Lzs,
+            );
+            for _ in 1..MAX_TEXT_LANG {
+                writer.push_blank();
+                writer.push_blank();
+            }
+        }
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        HqrArchive::from_reader(Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut json = Vec::new();
+        write_json(&mut json, &banks()).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains(r#""20": "Nouvelle partie""#));
+        assert_eq!(parse_json(&json).unwrap(), banks());
+    }
+
+    #[test]
+    fn test_po_round_trip() {
+        // untranslated entries are left out
+        let mut untranslated = banks();
+        untranslated[0].texts.push((22, "Quit".into()));
+        untranslated[1].texts.push((22, String::new()));
+
+        let mut po = Vec::new();
+        write_po(&mut po, &untranslated, Language::French).unwrap();
+        let po = String::from_utf8(po).unwrap();
+        assert!(po.contains("\"Language: fr\\n\""));
+        assert!(po.contains("msgctxt \"sys/20\"\nmsgid \"New Game\"\nmsgstr \"Nouvelle partie\""));
+        assert!(po.contains(r#"msgstr "Paramètres\001""#));
+        assert!(po.contains("msgid \"Quit\"\nmsgstr \"\"\n"));
+        assert_eq!(parse_po(&po).unwrap(), banks()[1..]);
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let mut archive = archive();
+        assert_eq!(read_banks(&mut archive).unwrap(), banks());
+
+        let edited = [BankTexts {
+            language: Language::French,
+            bank: 0,
+            texts: vec![(21, "Options".into()), (49, "Veuillez patienter".into())],
+        }];
+        let mut data = Vec::new();
+        rebuild(&mut archive, &edited)
+            .unwrap()
+            .write_to(&mut data)
+            .unwrap();
+        let mut rebuilt = HqrArchive::from_reader(Cursor::new(data)).unwrap();
+        assert_eq!(rebuilt.len(), archive.len());
+        for (index, info) in archive.infos().iter().enumerate() {
+            assert_eq!(rebuilt.info(index).unwrap().is_blank(), info.is_blank());
+        }
+
+        let banks = read_banks(&mut rebuilt).unwrap();
+        assert_eq!(banks[0], self::banks()[0]);
+        assert_eq!(
+            banks[1].texts,
+            [
+                (20, "Nouvelle partie".into()),
+                (21, "Options".into()),
+                (49, "Veuillez patienter".into())
+            ]
+        );
+
+        let invalid = [BankTexts {
+            texts: vec![(20, "€".into())],
+            ..edited[0].clone()
+        }];
+        assert!(rebuild(&mut archive, &invalid).is_err());
+
+        let missing = [BankTexts {
+            language: Language::Italian,
+            ..edited[0].clone()
+        }];
+        assert!(rebuild(&mut archive, &missing).is_err());
+    }
+}
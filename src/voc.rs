@@ -0,0 +1,126 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::hqr_ress::{DecodeError, Resource};
+
+const VOC_SIGNATURE: &[u8] = b"Creative Voice File\x1a";
+
+/// Codec of 8 bit unsigned PCM data.
+const CODEC_PCM_U8: u8 = 0;
+
+/// Mono 8 bit unsigned PCM sample decoded from a Creative VOC file.
+///
+/// The voice entries of the VOX archives replace the first byte of the VOC signature: a value
+/// other than `C` or 0 marks a text whose voice continues in a hidden entry, stored right after
+/// the data of the entry but missing from the offset table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    /// Samples per second
+    pub rate: u32,
+    pub data: Vec<u8>,
+    /// The voice continues in the next hidden entry of the archive.
+    pub continued: bool,
+}
+
+impl Sample {
+    /// Duration of the sample in milliseconds.
+    pub fn duration_ms(&self) -> u64 {
+        self.data.len() as u64 * 1000 / u64::from(self.rate.max(1))
+    }
+
+    /// Appends the data of `next`, which must have the same rate.
+    pub fn append(&mut self, next: Sample) -> Result<(), DecodeError> {
+        if next.rate != self.rate {
+            return Err(DecodeError::Invalid("sample rate of continued voice"));
+        }
+        self.data.extend_from_slice(&next.data);
+        self.continued = next.continued;
+        Ok(())
+    }
+}
+
+impl Resource for Sample {
+    type Error = DecodeError;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let header = data
+            .get(..VOC_SIGNATURE.len())
+            .ok_or(DecodeError::Truncated)?;
+        if header[1..] != VOC_SIGNATURE[1..] {
+            return Err(DecodeError::Invalid("voc signature"));
+        }
+        let continued = !matches!(header[0], b'C' | 0);
+
+        let mut reader = &data[VOC_SIGNATURE.len()..];
+        let header_size = usize::from(reader.read_u16::<LittleEndian>()?);
+        let mut reader = data.get(header_size..).ok_or(DecodeError::Truncated)?;
+
+        let mut rate = None;
+        let mut samples = Vec::new();
+        loop {
+            let block_type = match reader.read_u8() {
+                Ok(0) | Err(_) => break,
+                Ok(block_type) => block_type,
+            };
+            let size = reader.read_u24::<LittleEndian>()? as usize;
+            let block = reader.get(..size).ok_or(DecodeError::Truncated)?;
+            reader = &reader[size..];
+
+            match block_type {
+                // sound data
+                1 => {
+                    let [divisor, codec, pcm @ ..] = block else {
+                        return Err(DecodeError::Truncated);
+                    };
+                    if *codec != CODEC_PCM_U8 {
+                        return Err(DecodeError::Invalid("voc codec"));
+                    }
+                    rate.get_or_insert(1_000_000 / (256 - u32::from(*divisor)));
+                    samples.extend_from_slice(pcm);
+                }
+                // sound data continuation
+                2 => samples.extend_from_slice(block),
+                // silence, markers, text, repeats and extended blocks are not used by the game
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            rate: rate.ok_or(DecodeError::Invalid("voc without sound data"))?,
+            data: samples,
+            continued,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a VOC file with one sound data block at 1 MHz / (256 - `divisor`).
+    pub(crate) fn voc(first: u8, divisor: u8, pcm: &[u8]) -> Vec<u8> {
+        let mut data = VOC_SIGNATURE.to_vec();
+        data[0] = first;
+        data.extend_from_slice(&[26, 0, 0x0A, 0x01, 0x29, 0x11]);
+        let size = pcm.len() + 2;
+        data.extend_from_slice(&[1, size as u8, (size >> 8) as u8, (size >> 16) as u8]);
+        data.extend_from_slice(&[divisor, CODEC_PCM_U8]);
+        data.extend_from_slice(pcm);
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn test_decode_voc() {
+        let sample = Sample::decode(&voc(b'C', 166, &[128, 130, 126])).unwrap();
+        assert_eq!(sample.rate, 11111);
+        assert_eq!(sample.data, [128, 130, 126]);
+        assert!(!sample.continued);
+
+        let sample = Sample::decode(&voc(1, 166, &[128])).unwrap();
+        assert!(sample.continued);
+
+        let mut data = voc(b'C', 166, &[128]);
+        data[5] = b'X';
+        assert!(Sample::decode(&data).is_err());
+    }
+}